@@ -0,0 +1,46 @@
+//! Times recursive fibonacci under the tree-walking evaluator and the VM.
+//!
+//! Run with `cargo bench`, optionally passing `n`: `cargo bench -- 30`.
+
+use std::env;
+use std::time::{Duration, Instant};
+
+use monkey::compiler::Compiler;
+use monkey::environment::Environment;
+use monkey::evaluator::eval;
+use monkey::lexer::Lexer;
+use monkey::parser::Parser;
+use monkey::vm::Vm;
+
+const RUNS: u32 = 5;
+
+fn time<T>(mut f: impl FnMut() -> T) -> (T, Duration) {
+    let mut best = Duration::MAX;
+    let mut result = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        result = Some(f());
+        best = best.min(start.elapsed());
+    }
+    (result.expect("at least one run"), best)
+}
+
+fn main() {
+    // `cargo bench` passes `--bench` along with any arguments of ours.
+    let n: u32 = env::args().skip(1).find_map(|arg| arg.parse().ok()).unwrap_or(25);
+    let source = format!(
+        "let fib = fn(n) {{ if (n < 2) {{ return n; }} fib(n - 1) + fib(n - 2) }}; fib({n});"
+    );
+
+    let mut p = Parser::new(Lexer::new(source));
+    let program = p.parse_program().expect("benchmark source should parse");
+    let bytecode = Compiler::new().compile(&program).expect("benchmark source should compile");
+
+    let (evaluated, eval_time) = time(|| eval(&program, &Environment::new()));
+    let (executed, vm_time) = time(|| Vm::new(&bytecode).run().expect("benchmark should run"));
+    assert_eq!(evaluated, executed);
+
+    println!("fib({n}) = {executed}, best of {RUNS} runs");
+    println!("  evaluator: {eval_time:>12.3?}");
+    println!("  vm:        {vm_time:>12.3?}  ({:.1}x faster)", eval_time.as_secs_f64() / vm_time.as_secs_f64());
+}
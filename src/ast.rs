@@ -1,4 +1,8 @@
-#[derive(Debug, PartialEq, Eq)]
+use std::fmt;
+
+use crate::lexer::Span;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Precedence {
     Lowest = 1,
     Equals,
@@ -7,61 +11,297 @@ pub enum Precedence {
     Product,
     Prefix,
     Call,
+    Index,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Operator {
     Bang,
-    Minus,    
+    Minus,
+    Plus,
+    Asterisk,
+    Slash,
+    Lt,
+    Gt,
+    Eq,
+    NotEq,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct Program {
     pub statements: Vec<Statement>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     Let(Let),
     Return(Return),
     ExpressionStatement(ExpressionStatement),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     Ident(Ident),
     IntegerLiteral(IntegerLiteral),
+    FloatLiteral(FloatLiteral),
+    Boolean(Boolean),
+    StringLiteral(StringLiteral),
+    ArrayLiteral(ArrayLiteral),
+    HashLiteral(HashLiteral),
     Prefix(Prefix),
+    Infix(Infix),
+    If(If),
+    FunctionLiteral(FunctionLiteral),
+    Call(Call),
+    Index(Index),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Let {
     pub name: Ident,
     pub value: Expression,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Return {
     pub value: Expression,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct ExpressionStatement {
     pub value: Expression,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlockStatement {
+    pub statements: Vec<Statement>,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Ident {
     pub value: String,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct IntegerLiteral {
+    /// At most `i64::MAX`, or `i64::MIN`'s magnitude when it is the operand of a `-`.
     pub value: u64,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FloatLiteral {
+    pub value: f64,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Boolean {
+    pub value: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StringLiteral {
+    pub value: String,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Clone)]
+pub struct ArrayLiteral {
+    pub elements: Vec<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HashLiteral {
+    pub pairs: Vec<(Expression, Expression)>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Prefix {
     pub operator: Operator,
     pub right: Box<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Infix {
+    pub left: Box<Expression>,
+    pub operator: Operator,
+    pub right: Box<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct If {
+    pub condition: Box<Expression>,
+    pub consequence: BlockStatement,
+    pub alternative: Option<BlockStatement>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FunctionLiteral {
+    pub parameters: Vec<Ident>,
+    pub body: BlockStatement,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Call {
+    pub function: Box<Expression>,
+    pub arguments: Vec<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Index {
+    pub left: Box<Expression>,
+    pub index: Box<Expression>,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let(x) => x.span,
+            Statement::Return(x) => x.span,
+            Statement::ExpressionStatement(x) => x.span,
+        }
+    }
+}
+
+impl Expression {
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Ident(x) => x.span,
+            Expression::IntegerLiteral(x) => x.span,
+            Expression::FloatLiteral(x) => x.span,
+            Expression::Boolean(x) => x.span,
+            Expression::StringLiteral(x) => x.span,
+            Expression::ArrayLiteral(x) => x.span,
+            Expression::HashLiteral(x) => x.span,
+            Expression::Prefix(x) => x.span,
+            Expression::Infix(x) => x.span,
+            Expression::If(x) => x.span,
+            Expression::FunctionLiteral(x) => x.span,
+            Expression::Call(x) => x.span,
+            Expression::Index(x) => x.span,
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Bang => f.write_str("!"),
+            Operator::Minus => f.write_str("-"),
+            Operator::Plus => f.write_str("+"),
+            Operator::Asterisk => f.write_str("*"),
+            Operator::Slash => f.write_str("/"),
+            Operator::Lt => f.write_str("<"),
+            Operator::Gt => f.write_str(">"),
+            Operator::Eq => f.write_str("=="),
+            Operator::NotEq => f.write_str("!="),
+        }
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for stmt in &self.statements {
+            write!(f, "{stmt}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Let(s) => write!(f, "let {} = {};", s.name, s.value),
+            Statement::Return(s) => write!(f, "return {};", s.value),
+            Statement::ExpressionStatement(s) => write!(f, "{}", s.value),
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Ident(x) => write!(f, "{x}"),
+            Expression::IntegerLiteral(x) => write!(f, "{}", x.value),
+            Expression::FloatLiteral(x) => write!(f, "{:?}", x.value),
+            Expression::Boolean(x) => write!(f, "{}", x.value),
+            Expression::StringLiteral(x) => write!(f, "{}", Quoted(&x.value)),
+            Expression::ArrayLiteral(x) => {
+                let elements: Vec<String> = x.elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Expression::HashLiteral(x) => {
+                let pairs: Vec<String> = x.pairs.iter().map(|(k, v)| format!("{k}: {v}")).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Expression::Prefix(x) => write!(f, "({}{})", x.operator, x.right),
+            Expression::Infix(x) => write!(f, "({} {} {})", x.left, x.operator, x.right),
+            Expression::If(x) => {
+                write!(f, "if {} {{ {} }}", x.condition, x.consequence)?;
+                if let Some(alternative) = &x.alternative {
+                    write!(f, " else {{ {alternative} }}")?;
+                }
+                Ok(())
+            }
+            Expression::FunctionLiteral(x) => {
+                let parameters: Vec<String> = x.parameters.iter().map(|p| p.to_string()).collect();
+                write!(f, "fn({}) {{ {} }}", parameters.join(", "), x.body)
+            }
+            Expression::Call(x) => {
+                let arguments: Vec<String> = x.arguments.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", x.function, arguments.join(", "))
+            }
+            Expression::Index(x) => write!(f, "({}[{}])", x.left, x.index),
+        }
+    }
+}
+
+/// Displays a string as a double-quoted literal that lexes back to the same value.
+pub(crate) struct Quoted<'a>(pub &'a str);
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"")?;
+        for c in self.0.chars() {
+            match c {
+                '\n' => f.write_str("\\n")?,
+                '\t' => f.write_str("\\t")?,
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+                c => write!(f, "{c}")?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for stmt in &self.statements {
+            write!(f, "{stmt}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.value)
+    }
 }
@@ -0,0 +1,407 @@
+use std::fmt::{self, Write};
+
+/// A flat sequence of encoded instructions: an opcode byte followed by its
+/// operands, big-endian.
+pub type Instructions = Vec<u8>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum Opcode {
+    /// Pushes the constant at the given index of the constant pool.
+    Constant,
+    Pop,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    True,
+    False,
+    Null,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    Minus,
+    Bang,
+    /// Pops the condition and jumps to the given offset if it is falsy.
+    JumpNotTruthy,
+    Jump,
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,
+    /// Pushes a variable captured by the running closure.
+    GetFree,
+    /// Pushes the running closure itself, so functions can recurse.
+    CurrentClosure,
+    /// Collects the given number of stack values into an array.
+    Array,
+    /// Collects the given number of key/value pairs into a hash.
+    Hash,
+    Index,
+    /// Calls the function below the given number of arguments.
+    Call,
+    ReturnValue,
+    /// Returns `null` from a function with no final expression.
+    Return,
+    /// Wraps the function at the given index of the function table in a
+    /// closure, capturing the given number of free variables off the stack.
+    Closure,
+}
+
+impl Opcode {
+    /// Every opcode, indexed by its encoding.
+    const ALL: [Opcode; 30] = [
+        Opcode::Constant,
+        Opcode::Pop,
+        Opcode::Add,
+        Opcode::Sub,
+        Opcode::Mul,
+        Opcode::Div,
+        Opcode::True,
+        Opcode::False,
+        Opcode::Null,
+        Opcode::Equal,
+        Opcode::NotEqual,
+        Opcode::GreaterThan,
+        Opcode::LessThan,
+        Opcode::Minus,
+        Opcode::Bang,
+        Opcode::JumpNotTruthy,
+        Opcode::Jump,
+        Opcode::GetGlobal,
+        Opcode::SetGlobal,
+        Opcode::GetLocal,
+        Opcode::SetLocal,
+        Opcode::GetFree,
+        Opcode::CurrentClosure,
+        Opcode::Array,
+        Opcode::Hash,
+        Opcode::Index,
+        Opcode::Call,
+        Opcode::ReturnValue,
+        Opcode::Return,
+        Opcode::Closure,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        Opcode::ALL.get(byte as usize).copied()
+    }
+
+    /// The width in bytes of each of the opcode's operands.
+    pub fn operand_widths(self) -> &'static [usize] {
+        match self {
+            Opcode::Constant
+            | Opcode::JumpNotTruthy
+            | Opcode::Jump
+            | Opcode::GetGlobal
+            | Opcode::SetGlobal
+            | Opcode::Array
+            | Opcode::Hash => &[2],
+            Opcode::GetLocal | Opcode::SetLocal | Opcode::GetFree | Opcode::Call => &[1],
+            Opcode::Closure => &[2, 1],
+            _ => &[],
+        }
+    }
+
+    /// The encoded length of an instruction with this opcode.
+    pub fn width(self) -> usize {
+        1 + self.operand_widths().iter().sum::<usize>()
+    }
+}
+
+/// A value known at compile time, stored in the constant pool.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Constant {
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+/// Marks the instructions from `offset` up to the next entry as coming from
+/// source line `line`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Line {
+    pub offset: usize,
+    pub line: usize,
+}
+
+/// The compiled body of a function literal.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    /// Slots to reserve for locals, parameters included.
+    pub num_locals: usize,
+    pub num_parameters: usize,
+    /// The number of variables a closure over this function captures.
+    pub num_free: usize,
+    /// Debug info, ordered by offset. Empty if it was not kept.
+    pub lines: Vec<Line>,
+}
+
+/// The output of the compiler: the top-level code plus everything it refers to.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub lines: Vec<Line>,
+    pub constants: Vec<Constant>,
+    /// The name of each global slot, for reporting reads of unbound globals.
+    pub globals: Vec<String>,
+    pub functions: Vec<CompiledFunction>,
+}
+
+/// Encodes a single instruction. Operands are truncated to their width, so
+/// callers must range check them first.
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let mut instruction = Vec::with_capacity(op.width());
+    instruction.push(op as u8);
+
+    for (&operand, &width) in operands.iter().zip(op.operand_widths()) {
+        match width {
+            2 => instruction.extend_from_slice(&(operand as u16).to_be_bytes()),
+            1 => instruction.push(operand as u8),
+            _ => unreachable!("no operand is {width} bytes wide"),
+        }
+    }
+
+    instruction
+}
+
+/// Decodes the operands of `op` from the start of `ins`, returning them along
+/// with the number of bytes read. Returns `None` if `ins` is too short.
+pub fn read_operands(op: Opcode, ins: &[u8]) -> Option<(Vec<usize>, usize)> {
+    let mut operands = Vec::with_capacity(op.operand_widths().len());
+    let mut offset = 0;
+
+    for &width in op.operand_widths() {
+        let bytes = ins.get(offset..offset + width)?;
+        operands.push(bytes.iter().fold(0, |acc, &b| (acc << 8) | b as usize));
+        offset += width;
+    }
+
+    Some((operands, offset))
+}
+
+pub fn read_u16(ins: &[u8], offset: usize) -> usize {
+    u16::from_be_bytes([ins[offset], ins[offset + 1]]) as usize
+}
+
+/// Renders a human-readable listing of `bytecode`: the top-level code, then
+/// each function in the function table, then the constant pool.
+///
+/// Each instruction is shown with its offset, its source line if there is
+/// debug info, and its decoded operands, annotated with the constant it loads
+/// or the offset it jumps to. Malformed input is flagged in the listing rather
+/// than causing a panic.
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let mut out = String::from("== main ==\n");
+    disassemble_instructions(&mut out, &bytecode.instructions, &bytecode.lines, bytecode);
+
+    for (i, function) in bytecode.functions.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "\n== fn {i} (parameters: {}, locals: {}) ==",
+            function.num_parameters, function.num_locals
+        );
+        disassemble_instructions(&mut out, &function.instructions, &function.lines, bytecode);
+    }
+
+    if !bytecode.constants.is_empty() {
+        out.push_str("\n== constants ==\n");
+        for (i, constant) in bytecode.constants.iter().enumerate() {
+            let _ = writeln!(out, "{i:04} {constant}");
+        }
+    }
+
+    out
+}
+
+fn disassemble_instructions(out: &mut String, ins: &[u8], lines: &[Line], bytecode: &Bytecode) {
+    let mut offset = 0;
+    let has_lines = !lines.is_empty();
+    let mut lines = lines.iter().peekable();
+
+    while offset < ins.len() {
+        let _ = write!(out, "{offset:04} ");
+        if has_lines {
+            match lines.next_if(|line| line.offset <= offset) {
+                Some(line) => {
+                    let _ = write!(out, "{:>4} ", line.line);
+                }
+                None => out.push_str("   | "),
+            }
+        }
+
+        let Some(op) = Opcode::from_byte(ins[offset]) else {
+            let _ = writeln!(out, "<invalid opcode {:#04x}>", ins[offset]);
+            return;
+        };
+        let Some((operands, read)) = read_operands(op, &ins[offset + 1..]) else {
+            let _ = writeln!(out, "{op} <truncated>");
+            return;
+        };
+
+        let _ = write!(out, "{op}");
+        for operand in &operands {
+            let _ = write!(out, " {operand}");
+        }
+        let annotation = match op {
+            Opcode::Constant => Some(match bytecode.constants.get(operands[0]) {
+                Some(constant) => constant.to_string(),
+                None => "<invalid constant>".to_string(),
+            }),
+            Opcode::GetGlobal | Opcode::SetGlobal => Some(match bytecode.globals.get(operands[0]) {
+                Some(name) => name.clone(),
+                None => "<invalid global>".to_string(),
+            }),
+            Opcode::Jump | Opcode::JumpNotTruthy => Some(format!("-> {:04}", operands[0])),
+            Opcode::Closure => Some(match bytecode.functions.get(operands[0]) {
+                Some(_) => format!("fn {}", operands[0]),
+                None => "<invalid function>".to_string(),
+            }),
+            _ => None,
+        };
+        if let Some(annotation) = annotation {
+            let _ = write!(out, " ({annotation})");
+        }
+        out.push('\n');
+
+        offset += 1 + read;
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Op{self:?}")
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Integer(x) => write!(f, "{x}"),
+            Constant::Float(x) => write!(f, "{x:?}"),
+            Constant::String(x) => write!(f, "{x:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{disassemble, make, read_operands, Bytecode, CompiledFunction, Constant, Line, Opcode};
+
+    #[test]
+    fn test_opcode_encoding() {
+        for (i, op) in Opcode::ALL.iter().enumerate() {
+            assert_eq!(*op as u8 as usize, i);
+            assert_eq!(Opcode::from_byte(i as u8), Some(*op));
+        }
+        assert_eq!(Opcode::from_byte(Opcode::ALL.len() as u8), None);
+    }
+
+    #[test]
+    fn test_make() {
+        let tests = [
+            (Opcode::Constant, vec![65534], vec![Opcode::Constant as u8, 255, 254]),
+            (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+            (Opcode::GetLocal, vec![255], vec![Opcode::GetLocal as u8, 255]),
+            (Opcode::Closure, vec![65534, 255], vec![Opcode::Closure as u8, 255, 254, 255]),
+        ];
+
+        for (op, operands, expected) in tests {
+            assert_eq!(make(op, &operands), expected);
+        }
+    }
+
+    #[test]
+    fn test_read_operands() {
+        let tests = [
+            (Opcode::Constant, vec![65535], 2),
+            (Opcode::GetLocal, vec![255], 1),
+            (Opcode::Closure, vec![65535, 255], 3),
+        ];
+
+        for (op, operands, bytes_read) in tests {
+            let instruction = make(op, &operands);
+            assert_eq!(read_operands(op, &instruction[1..]), Some((operands, bytes_read)));
+        }
+
+        assert_eq!(read_operands(Opcode::Closure, &[0, 1]), None);
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let bytecode = Bytecode {
+            instructions: [
+                make(Opcode::Constant, &[0]),
+                make(Opcode::JumpNotTruthy, &[10]),
+                make(Opcode::Closure, &[0, 1]),
+                make(Opcode::SetGlobal, &[0]),
+            ]
+            .concat(),
+            lines: Vec::new(),
+            constants: vec![Constant::Integer(7), Constant::String("hi\n".to_string())],
+            globals: vec!["f".to_string()],
+            functions: vec![CompiledFunction {
+                instructions: [make(Opcode::GetFree, &[0]), make(Opcode::Constant, &[1]), make(Opcode::Add, &[])].concat(),
+                num_locals: 1,
+                num_parameters: 1,
+                num_free: 1,
+                lines: Vec::new(),
+            }],
+        };
+
+        let expected = "\
+== main ==
+0000 OpConstant 0 (7)
+0003 OpJumpNotTruthy 10 (-> 0010)
+0006 OpClosure 0 1 (fn 0)
+0010 OpSetGlobal 0 (f)
+
+== fn 0 (parameters: 1, locals: 1) ==
+0000 OpGetFree 0
+0002 OpConstant 1 (\"hi\\n\")
+0005 OpAdd
+
+== constants ==
+0000 7
+0001 \"hi\\n\"
+";
+        assert_eq!(disassemble(&bytecode), expected);
+    }
+
+    #[test]
+    fn test_disassemble_malformed() {
+        let bytecode = Bytecode {
+            instructions: vec![Opcode::Constant as u8, 0, 5, Opcode::Closure as u8, 0],
+            ..Bytecode::default()
+        };
+        assert_eq!(
+            disassemble(&bytecode),
+            "== main ==\n0000 OpConstant 5 (<invalid constant>)\n0003 OpClosure <truncated>\n"
+        );
+
+        let bytecode = Bytecode {
+            instructions: vec![Opcode::Pop as u8, 0xff, Opcode::Pop as u8],
+            ..Bytecode::default()
+        };
+        assert_eq!(disassemble(&bytecode), "== main ==\n0000 OpPop\n0001 <invalid opcode 0xff>\n");
+    }
+
+    #[test]
+    fn test_disassemble_with_lines() {
+        let bytecode = Bytecode {
+            instructions: [make(Opcode::True, &[]), make(Opcode::Pop, &[]), make(Opcode::Null, &[])].concat(),
+            lines: vec![Line { offset: 0, line: 1 }, Line { offset: 2, line: 3 }],
+            ..Bytecode::default()
+        };
+
+        let expected = "\
+== main ==
+0000    1 OpTrue
+0001    | OpPop
+0002    3 OpNull
+";
+        assert_eq!(disassemble(&bytecode), expected);
+    }
+}
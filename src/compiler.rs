@@ -0,0 +1,715 @@
+use std::collections::HashMap;
+use std::mem;
+
+use thiserror::Error;
+
+use crate::ast::{BlockStatement, Expression, FunctionLiteral, Operator, Program, Statement};
+use crate::code::{make, Bytecode, CompiledFunction, Constant, Instructions, Line, Opcode};
+use crate::lexer::Span;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CompileError {
+    #[error("too many {what}; the limit is {limit}")]
+    LimitExceeded {
+        what: &'static str,
+        limit: usize,
+        span: Span,
+    },
+}
+
+impl CompileError {
+    pub fn span(&self) -> Span {
+        match self {
+            CompileError::LimitExceeded { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum SymbolScope {
+    Global,
+    Local,
+    /// Captured from an enclosing function.
+    Free,
+    /// The name of the function being compiled, bound inside its own body.
+    Function,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Symbol {
+    scope: SymbolScope,
+    index: usize,
+}
+
+#[derive(Debug, Default)]
+struct SymbolTable {
+    store: HashMap<String, Symbol>,
+    num_definitions: usize,
+    /// The outer symbols captured by this function, in capture order.
+    free: Vec<Symbol>,
+    /// The name of each global slot. Only the outermost table has globals.
+    global_names: Vec<String>,
+    outer: Option<Box<SymbolTable>>,
+}
+
+impl SymbolTable {
+    fn enclosed(outer: SymbolTable) -> SymbolTable {
+        SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..SymbolTable::default()
+        }
+    }
+
+    fn define(&mut self, name: &str) -> Symbol {
+        if self.outer.is_none() {
+            return self.global(name);
+        }
+
+        let symbol = Symbol {
+            scope: SymbolScope::Local,
+            index: self.num_definitions,
+        };
+        self.num_definitions += 1;
+        self.store.insert(name.to_string(), symbol);
+        symbol
+    }
+
+    /// Returns the global slot for `name`, creating it if this is the first
+    /// mention. Rebinding a global reuses its slot, so functions that read it
+    /// see the new value.
+    fn global(&mut self, name: &str) -> Symbol {
+        if let Some(outer) = &mut self.outer {
+            return outer.global(name);
+        }
+        if let Some(symbol) = self.store.get(name) {
+            return *symbol;
+        }
+
+        let symbol = Symbol {
+            scope: SymbolScope::Global,
+            index: self.global_names.len(),
+        };
+        self.global_names.push(name.to_string());
+        self.store.insert(name.to_string(), symbol);
+        symbol
+    }
+
+    fn define_function_name(&mut self, name: &str) {
+        let symbol = Symbol {
+            scope: SymbolScope::Function,
+            index: 0,
+        };
+        self.store.insert(name.to_string(), symbol);
+    }
+
+    /// Resolves `name`, registering it as a free variable of every function
+    /// between its definition and this scope.
+    fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(*symbol);
+        }
+
+        let symbol = self.outer.as_mut()?.resolve(name)?;
+        if symbol.scope == SymbolScope::Global {
+            return Some(symbol);
+        }
+
+        self.free.push(symbol);
+        let free = Symbol {
+            scope: SymbolScope::Free,
+            index: self.free.len() - 1,
+        };
+        self.store.insert(name.to_string(), free);
+        Some(free)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+/// The instructions of the function currently being compiled.
+#[derive(Debug, Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    lines: Vec<Line>,
+    last: Option<EmittedInstruction>,
+    previous: Option<EmittedInstruction>,
+}
+
+impl CompilationScope {
+    /// Drops line entries left pointing past the end by removed instructions.
+    fn trim_lines(&mut self) {
+        let len = self.instructions.len();
+        self.lines.retain(|line| line.offset < len);
+    }
+}
+
+/// Lowers a [`Program`] to [`Bytecode`] for the [`Vm`](crate::vm::Vm).
+///
+/// Compiled programs behave like evaluated ones, with two exceptions:
+///
+/// - A closure copies the locals of enclosing functions it uses when it is
+///   created. It does not see those locals rebound later, and cannot use ones
+///   defined after it; the evaluator looks them up each time it runs. Globals
+///   are looked up at runtime in both, so forward references and rebinding
+///   at the top level work the same.
+/// - A program ending in a `let` produces the value of the last expression
+///   statement before it, where the evaluator produces `null`.
+#[derive(Debug, Default)]
+pub struct Compiler {
+    constants: Vec<Constant>,
+    functions: Vec<CompiledFunction>,
+    symbols: SymbolTable,
+    scopes: Vec<CompilationScope>,
+}
+
+const MAX_U16_OPERAND: usize = u16::MAX as usize;
+const MAX_U8_OPERAND: usize = u8::MAX as usize;
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler {
+            scopes: vec![CompilationScope::default()],
+            ..Compiler::default()
+        }
+    }
+
+    pub fn compile(mut self, program: &Program) -> Result<Bytecode, CompileError> {
+        for stmt in &program.statements {
+            self.compile_statement(stmt)?;
+        }
+
+        let mut main = self.scopes.pop().unwrap_or_default();
+        main.trim_lines();
+        Ok(Bytecode {
+            instructions: main.instructions,
+            lines: main.lines,
+            constants: self.constants,
+            globals: self.symbols.global_names,
+            functions: self.functions,
+        })
+    }
+
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<(), CompileError> {
+        self.mark_line(stmt.span().line);
+
+        match stmt {
+            Statement::Let(s) => {
+                // Compiling the value first means `let x = x + 1;` reads any
+                // outer `x`, as it does in the evaluator. Local functions are
+                // told their name instead so they can call themselves; global
+                // ones find themselves at runtime.
+                let is_local = self.symbols.outer.is_some();
+                match &s.value {
+                    Expression::FunctionLiteral(literal) if is_local => {
+                        self.compile_function(literal, Some(&s.name.value))?
+                    }
+                    value => self.compile_expression(value)?,
+                }
+
+                let symbol = self.symbols.define(&s.name.value);
+                match symbol.scope {
+                    SymbolScope::Global => {
+                        check_limit(symbol.index, MAX_U16_OPERAND, "global bindings", s.span)?;
+                        self.emit(Opcode::SetGlobal, &[symbol.index]);
+                    }
+                    _ => {
+                        check_limit(symbol.index, MAX_U8_OPERAND, "local bindings", s.span)?;
+                        self.emit(Opcode::SetLocal, &[symbol.index]);
+                    }
+                }
+            }
+            Statement::Return(s) => {
+                self.compile_expression(&s.value)?;
+                self.emit(Opcode::ReturnValue, &[]);
+            }
+            Statement::ExpressionStatement(s) => {
+                self.compile_expression(&s.value)?;
+                self.emit(Opcode::Pop, &[]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expr: &Expression) -> Result<(), CompileError> {
+        match expr {
+            Expression::Ident(x) => {
+                // Anything not bound in an enclosing scope is taken to be a
+                // global, which may be defined later or not at all; the VM
+                // reports a missing one when it is read.
+                let symbol = match self.symbols.resolve(&x.value) {
+                    Some(symbol) => symbol,
+                    None => self.symbols.global(&x.value),
+                };
+                if symbol.scope == SymbolScope::Global {
+                    check_limit(symbol.index, MAX_U16_OPERAND, "global bindings", x.span)?;
+                }
+                self.load_symbol(symbol);
+            }
+            Expression::IntegerLiteral(x) => {
+                let value = i64::try_from(x.value).unwrap_or_else(|_| {
+                    unreachable!("the parser never produces integer literal {} outside a `-`", x.value)
+                });
+                self.emit_constant(Constant::Integer(value), x.span)?;
+            }
+            Expression::FloatLiteral(x) => self.emit_constant(Constant::Float(x.value), x.span)?,
+            Expression::Boolean(x) => {
+                self.emit(if x.value { Opcode::True } else { Opcode::False }, &[]);
+            }
+            Expression::StringLiteral(x) => self.emit_constant(Constant::String(x.value.clone()), x.span)?,
+            Expression::ArrayLiteral(x) => {
+                check_limit(x.elements.len(), MAX_U16_OPERAND, "array elements", x.span)?;
+                for element in &x.elements {
+                    self.compile_expression(element)?;
+                }
+                self.emit(Opcode::Array, &[x.elements.len()]);
+            }
+            Expression::HashLiteral(x) => {
+                check_limit(x.pairs.len(), MAX_U16_OPERAND, "hash pairs", x.span)?;
+                for (key, value) in &x.pairs {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+                self.emit(Opcode::Hash, &[x.pairs.len()]);
+            }
+            Expression::Prefix(x) => {
+                // `i64::MIN` has no positive counterpart, so its literal is
+                // only in range when negated.
+                if let (Operator::Minus, Expression::IntegerLiteral(literal)) = (&x.operator, &*x.right) {
+                    if literal.value == i64::MIN.unsigned_abs() {
+                        return self.emit_constant(Constant::Integer(i64::MIN), x.span);
+                    }
+                }
+
+                self.compile_expression(&x.right)?;
+                match x.operator {
+                    Operator::Bang => self.emit(Opcode::Bang, &[]),
+                    Operator::Minus => self.emit(Opcode::Minus, &[]),
+                    operator => unreachable!("the parser never produces a prefix {operator}"),
+                };
+            }
+            Expression::Infix(x) => {
+                self.compile_expression(&x.left)?;
+                self.compile_expression(&x.right)?;
+                let opcode = match x.operator {
+                    Operator::Plus => Opcode::Add,
+                    Operator::Minus => Opcode::Sub,
+                    Operator::Asterisk => Opcode::Mul,
+                    Operator::Slash => Opcode::Div,
+                    Operator::Lt => Opcode::LessThan,
+                    Operator::Gt => Opcode::GreaterThan,
+                    Operator::Eq => Opcode::Equal,
+                    Operator::NotEq => Opcode::NotEqual,
+                    Operator::Bang => unreachable!("the parser never produces an infix !"),
+                };
+                self.emit(opcode, &[]);
+            }
+            Expression::If(x) => {
+                self.compile_expression(&x.condition)?;
+                let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[0]);
+
+                self.compile_block_value(&x.consequence)?;
+                let jump = self.emit(Opcode::Jump, &[0]);
+                self.patch_jump(jump_not_truthy, x.span)?;
+
+                match &x.alternative {
+                    Some(alternative) => self.compile_block_value(alternative)?,
+                    None => {
+                        self.emit(Opcode::Null, &[]);
+                    }
+                }
+                self.patch_jump(jump, x.span)?;
+            }
+            Expression::FunctionLiteral(x) => self.compile_function(x, None)?,
+            Expression::Call(x) => {
+                check_limit(x.arguments.len(), MAX_U8_OPERAND, "call arguments", x.span)?;
+                self.compile_expression(&x.function)?;
+                for argument in &x.arguments {
+                    self.compile_expression(argument)?;
+                }
+                self.emit(Opcode::Call, &[x.arguments.len()]);
+            }
+            Expression::Index(x) => {
+                self.compile_expression(&x.left)?;
+                self.compile_expression(&x.index)?;
+                self.emit(Opcode::Index, &[]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles a block that is used as a value, leaving exactly one object on
+    /// the stack: the value of its final expression, or `null`.
+    fn compile_block_value(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+        for stmt in &block.statements {
+            self.compile_statement(stmt)?;
+        }
+
+        if self.last_instruction_is(Opcode::Pop) {
+            self.remove_last_instruction();
+        } else {
+            self.emit(Opcode::Null, &[]);
+        }
+
+        Ok(())
+    }
+
+    fn compile_function(&mut self, literal: &FunctionLiteral, name: Option<&str>) -> Result<(), CompileError> {
+        self.enter_scope();
+        if let Some(name) = name {
+            self.symbols.define_function_name(name);
+        }
+        for parameter in &literal.parameters {
+            self.symbols.define(&parameter.value);
+        }
+
+        for stmt in &literal.body.statements {
+            self.compile_statement(stmt)?;
+        }
+        if self.last_instruction_is(Opcode::Pop) {
+            self.remove_last_instruction();
+            self.emit(Opcode::ReturnValue, &[]);
+        } else if !self.last_instruction_is(Opcode::ReturnValue) {
+            self.emit(Opcode::Return, &[]);
+        }
+
+        let (function, free) = self.leave_scope(literal.parameters.len());
+        check_limit(function.num_parameters, MAX_U8_OPERAND, "parameters", literal.span)?;
+        check_limit(function.num_locals, MAX_U8_OPERAND + 1, "local bindings", literal.span)?;
+        check_limit(free.len(), MAX_U8_OPERAND, "captured variables", literal.span)?;
+        check_limit(function.instructions.len(), MAX_U16_OPERAND, "bytes of code in one function", literal.span)?;
+        check_limit(self.functions.len(), MAX_U16_OPERAND, "functions", literal.span)?;
+
+        for symbol in &free {
+            self.load_symbol(*symbol);
+        }
+        self.functions.push(function);
+        self.emit(Opcode::Closure, &[self.functions.len() - 1, free.len()]);
+
+        Ok(())
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        let outer = mem::take(&mut self.symbols);
+        self.symbols = SymbolTable::enclosed(outer);
+    }
+
+    /// Finishes the innermost function, returning it along with the outer
+    /// symbols it captures.
+    fn leave_scope(&mut self, num_parameters: usize) -> (CompiledFunction, Vec<Symbol>) {
+        let mut scope = self.scopes.pop().unwrap_or_default();
+        scope.trim_lines();
+        let outer = self.symbols.outer.take().map(|outer| *outer).unwrap_or_default();
+        let symbols = mem::replace(&mut self.symbols, outer);
+
+        let function = CompiledFunction {
+            instructions: scope.instructions,
+            num_locals: symbols.num_definitions,
+            num_parameters,
+            num_free: symbols.free.len(),
+            lines: scope.lines,
+        };
+        (function, symbols.free)
+    }
+
+    fn load_symbol(&mut self, symbol: Symbol) {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::GetGlobal, &[symbol.index]),
+            SymbolScope::Local => self.emit(Opcode::GetLocal, &[symbol.index]),
+            SymbolScope::Free => self.emit(Opcode::GetFree, &[symbol.index]),
+            SymbolScope::Function => self.emit(Opcode::CurrentClosure, &[]),
+        };
+    }
+
+    fn emit_constant(&mut self, constant: Constant, span: Span) -> Result<(), CompileError> {
+        check_limit(self.constants.len(), MAX_U16_OPERAND, "constants", span)?;
+        self.constants.push(constant);
+        self.emit(Opcode::Constant, &[self.constants.len() - 1]);
+        Ok(())
+    }
+
+    /// Appends an instruction to the current scope, returning its position.
+    fn emit(&mut self, opcode: Opcode, operands: &[usize]) -> usize {
+        let scope = self.scope();
+        let position = scope.instructions.len();
+        scope.instructions.extend(make(opcode, operands));
+        scope.previous = scope.last.replace(EmittedInstruction { opcode, position });
+        position
+    }
+
+    /// Attributes the instructions emitted from here on to source line `line`.
+    fn mark_line(&mut self, line: usize) {
+        let scope = self.scope();
+        let offset = scope.instructions.len();
+        match scope.lines.last_mut() {
+            Some(last) if last.line == line => {}
+            Some(last) if last.offset == offset => last.line = line,
+            _ => scope.lines.push(Line { offset, line }),
+        }
+    }
+
+    /// Points the jump at `position` to the next instruction to be emitted.
+    fn patch_jump(&mut self, position: usize, span: Span) -> Result<(), CompileError> {
+        let scope = self.scope();
+        let target = scope.instructions.len();
+        check_limit(target, MAX_U16_OPERAND, "bytes of code in one function", span)?;
+
+        let opcode = Opcode::from_byte(scope.instructions[position]).expect("patching an emitted jump");
+        let patched = make(opcode, &[target]);
+        scope.instructions[position..position + patched.len()].copy_from_slice(&patched);
+        Ok(())
+    }
+
+    fn last_instruction_is(&mut self, opcode: Opcode) -> bool {
+        self.scope().last.is_some_and(|last| last.opcode == opcode)
+    }
+
+    fn remove_last_instruction(&mut self) {
+        let scope = self.scope();
+        if let Some(last) = scope.last.take() {
+            scope.instructions.truncate(last.position);
+            scope.last = scope.previous.take();
+        }
+    }
+
+    fn scope(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().expect("the main scope is never left")
+    }
+}
+
+fn check_limit(count: usize, limit: usize, what: &'static str, span: Span) -> Result<(), CompileError> {
+    if count > limit {
+        return Err(CompileError::LimitExceeded { what, limit, span });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CompileError, Compiler};
+    use crate::code::{make, Bytecode, CompiledFunction, Constant, Line, Opcode};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn compile(input: &str) -> Result<Bytecode, CompileError> {
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+        Compiler::new().compile(&program)
+    }
+
+    fn concat(instructions: &[(Opcode, &[usize])]) -> Vec<u8> {
+        instructions.iter().flat_map(|(op, operands)| make(*op, operands)).collect()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let bytecode = compile("1 + 2; -3 * 4").unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            concat(&[
+                (Opcode::Constant, &[0]),
+                (Opcode::Constant, &[1]),
+                (Opcode::Add, &[]),
+                (Opcode::Pop, &[]),
+                (Opcode::Constant, &[2]),
+                (Opcode::Minus, &[]),
+                (Opcode::Constant, &[3]),
+                (Opcode::Mul, &[]),
+                (Opcode::Pop, &[]),
+            ])
+        );
+        assert_eq!(
+            bytecode.constants,
+            [1, 2, 3, 4].map(Constant::Integer).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let bytecode = compile("if (true) { 10 }; 3333;").unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            concat(&[
+                (Opcode::True, &[]),
+                (Opcode::JumpNotTruthy, &[10]),
+                (Opcode::Constant, &[0]),
+                (Opcode::Jump, &[11]),
+                (Opcode::Null, &[]),
+                (Opcode::Pop, &[]),
+                (Opcode::Constant, &[1]),
+                (Opcode::Pop, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        let bytecode = compile("let one = 1; let two = one; two;").unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            concat(&[
+                (Opcode::Constant, &[0]),
+                (Opcode::SetGlobal, &[0]),
+                (Opcode::GetGlobal, &[0]),
+                (Opcode::SetGlobal, &[1]),
+                (Opcode::GetGlobal, &[1]),
+                (Opcode::Pop, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let bytecode = compile("fn(a) { let b = a; }; fn() { return 1; 2 }").unwrap();
+
+        assert_eq!(
+            bytecode.functions,
+            vec![
+                CompiledFunction {
+                    instructions: concat(&[
+                        (Opcode::GetLocal, &[0]),
+                        (Opcode::SetLocal, &[1]),
+                        (Opcode::Return, &[]),
+                    ]),
+                    num_locals: 2,
+                    num_parameters: 1,
+                    num_free: 0,
+                    lines: vec![Line { offset: 0, line: 1 }],
+                },
+                CompiledFunction {
+                    instructions: concat(&[
+                        (Opcode::Constant, &[0]),
+                        (Opcode::ReturnValue, &[]),
+                        (Opcode::Constant, &[1]),
+                        (Opcode::ReturnValue, &[]),
+                    ]),
+                    num_locals: 0,
+                    num_parameters: 0,
+                    num_free: 0,
+                    lines: vec![Line { offset: 0, line: 1 }],
+                },
+            ]
+        );
+        assert_eq!(
+            bytecode.instructions,
+            concat(&[
+                (Opcode::Closure, &[0, 0]),
+                (Opcode::Pop, &[]),
+                (Opcode::Closure, &[1, 0]),
+                (Opcode::Pop, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let bytecode = compile("fn(a) { fn(b) { fn(c) { a + b + c } } };").unwrap();
+
+        assert_eq!(
+            bytecode.functions[0].instructions,
+            concat(&[
+                (Opcode::GetFree, &[0]),
+                (Opcode::GetFree, &[1]),
+                (Opcode::Add, &[]),
+                (Opcode::GetLocal, &[0]),
+                (Opcode::Add, &[]),
+                (Opcode::ReturnValue, &[]),
+            ])
+        );
+        assert_eq!(
+            bytecode.functions[1].instructions,
+            concat(&[
+                (Opcode::GetFree, &[0]),
+                (Opcode::GetLocal, &[0]),
+                (Opcode::Closure, &[0, 2]),
+                (Opcode::ReturnValue, &[]),
+            ])
+        );
+        assert_eq!(
+            bytecode.functions[2].instructions,
+            concat(&[
+                (Opcode::GetLocal, &[0]),
+                (Opcode::Closure, &[1, 1]),
+                (Opcode::ReturnValue, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        let bytecode = compile("let f = fn(x) { f(x) }; fn() { let g = fn() { g() }; }").unwrap();
+
+        let recursive_call = concat(&[(Opcode::CurrentClosure, &[]), (Opcode::Call, &[0]), (Opcode::ReturnValue, &[])]);
+        assert_eq!(
+            bytecode.functions[0].instructions,
+            concat(&[
+                (Opcode::GetGlobal, &[0]),
+                (Opcode::GetLocal, &[0]),
+                (Opcode::Call, &[1]),
+                (Opcode::ReturnValue, &[]),
+            ])
+        );
+        assert_eq!(bytecode.functions[1].instructions, recursive_call);
+    }
+
+    #[test]
+    fn test_globals_resolve_at_runtime() {
+        let bytecode = compile("let f = fn() { g }; let g = 1; let g = 2; h").unwrap();
+
+        assert_eq!(bytecode.globals, ["g", "f", "h"]);
+        assert_eq!(
+            bytecode.functions[0].instructions,
+            concat(&[(Opcode::GetGlobal, &[0]), (Opcode::ReturnValue, &[])])
+        );
+        assert_eq!(
+            bytecode.instructions,
+            concat(&[
+                (Opcode::Closure, &[0, 0]),
+                (Opcode::SetGlobal, &[1]),
+                (Opcode::Constant, &[0]),
+                (Opcode::SetGlobal, &[0]),
+                (Opcode::Constant, &[1]),
+                (Opcode::SetGlobal, &[0]),
+                (Opcode::GetGlobal, &[2]),
+                (Opcode::Pop, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let arguments = vec!["0"; 256].join(", ");
+        let tests = [(format!("f({arguments})"), "too many call arguments; the limit is 255")];
+
+        for (input, expected) in tests {
+            assert_eq!(compile(&input).unwrap_err().to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_line_table() {
+        let bytecode = compile("let a = 1;\n\nlet f = fn() {\n  a;\n  a\n};\nf();").unwrap();
+
+        assert_eq!(
+            bytecode.lines,
+            vec![
+                Line { offset: 0, line: 1 },
+                Line { offset: 6, line: 3 },
+                Line { offset: 13, line: 7 },
+            ]
+        );
+        assert_eq!(
+            bytecode.functions[0].lines,
+            vec![Line { offset: 0, line: 4 }, Line { offset: 4, line: 5 }]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt::Write;
+
+use crate::compiler::CompileError;
+use crate::lexer::{LexError, Span};
+use crate::parser::ParseError;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// An error message tied to a region of source, plus any help notes worth
+/// showing underneath it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Diagnostic {
+        Diagnostic {
+            message: message.into(),
+            span,
+            help: Vec::new(),
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Renders the diagnostic in the style of rustc: the message, a pointer to
+    /// `name:line:column`, the offending source line with the span underlined,
+    /// and any help notes. `color` switches on ANSI escapes.
+    pub fn render(&self, name: &str, source: &str, color: bool) -> String {
+        let paint = |style: &'static str| if color { style } else { "" };
+        let (red, blue, bold, reset) = (paint(RED), paint(BLUE), paint(BOLD), paint(RESET));
+
+        let start = self.span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+        let line = &source[line_start..line_end];
+
+        // Tabs are kept so the carets stay aligned with the echoed line.
+        let padding: String = source[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let end = self.span.end.clamp(start, line_end);
+        let width = source[start..end].chars().count().max(1);
+
+        let line_number = self.span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{red}error{reset}{bold}: {}{reset}", self.message);
+        let _ = writeln!(out, "{gutter}{blue}-->{reset} {name}:{}", self.span);
+        let _ = writeln!(out, "{gutter} {blue}|{reset}");
+        let _ = writeln!(out, "{blue}{line_number} |{reset} {line}");
+        let _ = writeln!(out, "{gutter} {blue}|{reset} {padding}{red}{}{reset}", "^".repeat(width));
+        for help in &self.help {
+            let _ = writeln!(out, "{gutter} {blue}={reset} {bold}help{reset}: {help}");
+        }
+        out
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(err: &ParseError) -> Diagnostic {
+        let diagnostic = Diagnostic::error(err.to_string(), err.span());
+
+        match err {
+            ParseError::IntegerOverflow { .. } => {
+                diagnostic.with_help(format!("the largest integer literal is {}", i64::MAX))
+            }
+            ParseError::Lex { error: LexError::UnknownEscape(_), .. } => {
+                diagnostic.with_help(r#"valid escapes are \n, \t, \", \\ and \u{...}"#)
+            }
+            ParseError::Lex { error: LexError::InvalidUnicodeEscape(_), .. } => {
+                diagnostic.with_help("unicode escapes take 1 to 6 hex digits naming a valid character")
+            }
+            _ => diagnostic,
+        }
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(err: &CompileError) -> Diagnostic {
+        Diagnostic::error(err.to_string(), err.span())
+    }
+}
+
+/// Renders every parse error against `source`, separated by blank lines.
+pub fn render_parse_errors(errors: &[ParseError], name: &str, source: &str, color: bool) -> String {
+    errors
+        .iter()
+        .map(|err| Diagnostic::from(err).render(name, source, color))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render_parse_errors, Diagnostic};
+    use crate::lexer::{Lexer, Span};
+    use crate::parser::Parser;
+
+    #[test]
+    fn render_plain() {
+        let source = "let x = 1;\nlet y = (x + 2;\n";
+
+        let mut p = Parser::new(Lexer::new(source.into()));
+        let errors = p.parse_program().expect_err("parser should fail");
+
+        let expected = "\
+error: expected `)`, found `;`
+ --> test.mk:2:15
+  |
+2 | let y = (x + 2;
+  |               ^
+";
+        assert_eq!(render_parse_errors(&errors, "test.mk", source, false), expected);
+    }
+
+    #[test]
+    fn render_underlines_whole_span_with_help() {
+        let source = "\tlet big = 99999999999999999999;";
+
+        let mut p = Parser::new(Lexer::new(source.into()));
+        let errors = p.parse_program().expect_err("parser should fail");
+
+        let expected = "\
+error: integer literal `99999999999999999999` is out of range
+ --> <repl>:1:12
+  |
+1 | \tlet big = 99999999999999999999;
+  | \t          ^^^^^^^^^^^^^^^^^^^^
+  = help: the largest integer literal is 9223372036854775807
+";
+        assert_eq!(render_parse_errors(&errors, "<repl>", source, false), expected);
+    }
+
+    #[test]
+    fn render_at_end_of_input() {
+        let source = "if (x) {";
+        let diagnostic = Diagnostic::error(
+            "expected `}`, found end of input",
+            Span { start: 8, end: 8, line: 1, column: 9 },
+        );
+
+        let expected = "\
+error: expected `}`, found end of input
+ --> <repl>:1:9
+  |
+1 | if (x) {
+  |         ^
+";
+        assert_eq!(diagnostic.render("<repl>", source, false), expected);
+    }
+
+    #[test]
+    fn render_colored() {
+        let diagnostic = Diagnostic::error("oops", Span { start: 0, end: 1, line: 1, column: 1 });
+        let rendered = diagnostic.render("<repl>", "@", true);
+
+        assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m"));
+        assert!(rendered.contains("\x1b[1;31m^\x1b[0m"));
+    }
+}
@@ -0,0 +1,111 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::object::Object;
+
+/// A scope of bindings, optionally nested inside an outer one.
+///
+/// `Environment` is a shared handle: clones refer to the same scope, which is
+/// how closures keep their defining scope alive after it has been left.
+///
+/// A function bound in the scope it captures, as every `let`-bound recursive
+/// function is, forms a reference cycle, so that scope is never freed. This is
+/// left as is: scopes live for a whole REPL session or script run anyway.
+#[derive(Clone, Default)]
+pub struct Environment {
+    scope: Rc<RefCell<Scope>>,
+}
+
+#[derive(Default)]
+struct Scope {
+    store: HashMap<String, Object>,
+    outer: Option<Environment>,
+    /// How many function calls deep the scope was created.
+    call_depth: usize,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    /// Creates a fresh scope whose lookups fall back to `outer`.
+    pub fn enclosed(outer: &Environment) -> Environment {
+        Environment::enclosed_at(outer, outer.call_depth())
+    }
+
+    /// Creates the scope for a call from `caller` to a function defined in
+    /// `outer`, one call deeper than `caller`.
+    pub fn enclosed_for_call(outer: &Environment, caller: &Environment) -> Environment {
+        Environment::enclosed_at(outer, caller.call_depth() + 1)
+    }
+
+    fn enclosed_at(outer: &Environment, call_depth: usize) -> Environment {
+        Environment {
+            scope: Rc::new(RefCell::new(Scope {
+                store: HashMap::new(),
+                outer: Some(outer.clone()),
+                call_depth,
+            })),
+        }
+    }
+
+    pub fn call_depth(&self) -> usize {
+        self.scope.borrow().call_depth
+    }
+
+    /// Looks `name` up in this scope, then in each enclosing scope in turn.
+    pub fn get(&self, name: &str) -> Option<Object> {
+        let scope = self.scope.borrow();
+        match scope.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => scope.outer.as_ref()?.get(name),
+        }
+    }
+
+    /// Binds `name` in this scope, shadowing any outer binding of the same name.
+    pub fn set(&self, name: impl Into<String>, value: Object) {
+        self.scope.borrow_mut().store.insert(name.into(), value);
+    }
+}
+
+/// Two environments are equal only if they are the same scope.
+impl PartialEq for Environment {
+    fn eq(&self, other: &Environment) -> bool {
+        Rc::ptr_eq(&self.scope, &other.scope)
+    }
+}
+
+// A recursive function is stored in the scope it captures, so printing the
+// bindings could recurse forever.
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Environment").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Environment;
+    use crate::object::Object;
+
+    #[test]
+    fn test_enclosed_scopes() {
+        let outer = Environment::new();
+        outer.set("x", Object::Integer(1));
+        outer.set("y", Object::Integer(2));
+
+        let inner = Environment::enclosed(&outer);
+        inner.set("x", Object::Integer(10));
+
+        assert_eq!(inner.get("x"), Some(Object::Integer(10)));
+        assert_eq!(inner.get("y"), Some(Object::Integer(2)));
+        assert_eq!(inner.get("z"), None);
+        assert_eq!(outer.get("x"), Some(Object::Integer(1)));
+
+        outer.set("z", Object::Integer(3));
+        assert_eq!(inner.get("z"), Some(Object::Integer(3)));
+    }
+}
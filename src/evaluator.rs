@@ -0,0 +1,575 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use crate::ast::{BlockStatement, Call, Expression, If, Index, Infix, Operator, Prefix, Program, Statement};
+use crate::environment::Environment;
+use crate::object::{Function, Object};
+
+/// How deeply function calls may nest, matching the VM's frame limit.
+const MAX_CALL_DEPTH: usize = 1024;
+
+/// The native stack `eval` needs to nest ordinary function calls up to its
+/// call depth limit, with room to spare in debug builds.
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Evaluates `program` statement by statement, returning the value of the last
+/// one. Evaluation stops early at a `return` or at the first error.
+///
+/// Each nested call recurses on the native stack, several KiB per call in
+/// debug builds, so a default 2 MiB thread overflows after a few hundred calls,
+/// well short of the limit. Run `eval` on a thread with at least
+/// [`STACK_SIZE`] bytes of stack, as the `monkey` binary does.
+pub fn eval(program: &Program, env: &Environment) -> Object {
+    let mut result = Object::Null;
+
+    for stmt in &program.statements {
+        result = eval_statement(stmt, env);
+
+        match result {
+            Object::ReturnValue(value) => return *value,
+            Object::Error(_) => return result,
+            _ => {}
+        }
+    }
+
+    result
+}
+
+/// Like [`eval`], but a `return` stays wrapped so it keeps unwinding through
+/// any enclosing blocks.
+fn eval_block_statement(block: &BlockStatement, env: &Environment) -> Object {
+    let mut result = Object::Null;
+
+    for stmt in &block.statements {
+        result = eval_statement(stmt, env);
+
+        if matches!(result, Object::ReturnValue(_) | Object::Error(_)) {
+            return result;
+        }
+    }
+
+    result
+}
+
+fn eval_statement(stmt: &Statement, env: &Environment) -> Object {
+    match stmt {
+        Statement::Let(s) => {
+            let value = eval_expression(&s.value, env);
+            if value.is_error() {
+                return value;
+            }
+            env.set(s.name.value.clone(), value);
+            Object::Null
+        }
+        Statement::Return(s) => {
+            let value = eval_expression(&s.value, env);
+            if value.is_error() {
+                return value;
+            }
+            Object::ReturnValue(Box::new(value))
+        }
+        Statement::ExpressionStatement(s) => eval_expression(&s.value, env),
+    }
+}
+
+fn eval_expression(expr: &Expression, env: &Environment) -> Object {
+    match expr {
+        Expression::Ident(x) => match env.get(&x.value) {
+            Some(value) => value,
+            None => Object::Error(format!("identifier not found: {}", x.value)),
+        },
+        Expression::IntegerLiteral(x) => match i64::try_from(x.value) {
+            Ok(value) => Object::Integer(value),
+            Err(_) => unreachable!("the parser never produces integer literal {} outside a `-`", x.value),
+        },
+        Expression::FloatLiteral(x) => Object::Float(x.value),
+        Expression::Boolean(x) => Object::Boolean(x.value),
+        Expression::StringLiteral(x) => Object::String(x.value.clone()),
+        Expression::ArrayLiteral(x) => match eval_expressions(&x.elements, env) {
+            Ok(elements) => Object::Array(elements),
+            Err(err) => err,
+        },
+        Expression::HashLiteral(x) => {
+            let mut pairs = BTreeMap::new();
+            for (key, value) in &x.pairs {
+                let key = eval_expression(key, env);
+                if key.is_error() {
+                    return key;
+                }
+                let Some(key) = key.hash_key() else {
+                    return Object::Error(format!("unusable as hash key: {}", key.type_name()));
+                };
+                let value = eval_expression(value, env);
+                if value.is_error() {
+                    return value;
+                }
+                pairs.insert(key, value);
+            }
+            Object::Hash(pairs)
+        }
+        Expression::Prefix(x) => eval_prefix_expression(x, env),
+        Expression::Infix(x) => eval_infix_expression(x, env),
+        Expression::If(x) => eval_if_expression(x, env),
+        Expression::FunctionLiteral(x) => Object::Function(Rc::new(Function {
+            parameters: x.parameters.clone(),
+            body: x.body.clone(),
+            env: env.clone(),
+        })),
+        Expression::Call(x) => eval_call_expression(x, env),
+        Expression::Index(x) => eval_index_expression(x, env),
+    }
+}
+
+/// Evaluates each expression in turn, stopping at the first error.
+fn eval_expressions(exprs: &[Expression], env: &Environment) -> Result<Vec<Object>, Object> {
+    let mut values = Vec::with_capacity(exprs.len());
+    for expr in exprs {
+        let value = eval_expression(expr, env);
+        if value.is_error() {
+            return Err(value);
+        }
+        values.push(value);
+    }
+    Ok(values)
+}
+
+fn eval_prefix_expression(prefix: &Prefix, env: &Environment) -> Object {
+    // `i64::MIN` has no positive counterpart, so its literal is only in range
+    // when negated.
+    if let (Operator::Minus, Expression::IntegerLiteral(literal)) = (&prefix.operator, &*prefix.right) {
+        if literal.value == i64::MIN.unsigned_abs() {
+            return Object::Integer(i64::MIN);
+        }
+    }
+
+    let right = eval_expression(&prefix.right, env);
+    if right.is_error() {
+        return right;
+    }
+
+    eval_prefix(&prefix.operator, right)
+}
+
+/// Applies a unary operator to an already evaluated operand.
+pub(crate) fn eval_prefix(operator: &Operator, right: Object) -> Object {
+    match (operator, right) {
+        (Operator::Bang, right) => Object::Boolean(!right.is_truthy()),
+        (Operator::Minus, Object::Integer(x)) => match x.checked_neg() {
+            Some(value) => Object::Integer(value),
+            None => Object::Error(format!("integer overflow: -{x}")),
+        },
+        (Operator::Minus, Object::Float(x)) => Object::Float(-x),
+        (operator, right) => Object::Error(format!("unknown operator: {operator}{}", right.type_name())),
+    }
+}
+
+fn eval_infix_expression(infix: &Infix, env: &Environment) -> Object {
+    let left = eval_expression(&infix.left, env);
+    if left.is_error() {
+        return left;
+    }
+    let right = eval_expression(&infix.right, env);
+    if right.is_error() {
+        return right;
+    }
+
+    eval_infix(&infix.operator, left, right)
+}
+
+/// Applies a binary operator to two already evaluated operands.
+pub(crate) fn eval_infix(operator: &Operator, left: Object, right: Object) -> Object {
+    match (left, right) {
+        (Object::Integer(l), Object::Integer(r)) => eval_integer_infix(operator, l, r),
+        (Object::Float(l), Object::Float(r)) => eval_float_infix(operator, l, r),
+        (Object::Integer(l), Object::Float(r)) => eval_float_infix(operator, l as f64, r),
+        (Object::Float(l), Object::Integer(r)) => eval_float_infix(operator, l, r as f64),
+        (Object::String(l), Object::String(r)) => match operator {
+            Operator::Plus => Object::String(l + &r),
+            Operator::Eq => Object::Boolean(l == r),
+            Operator::NotEq => Object::Boolean(l != r),
+            _ => Object::Error(format!("unknown operator: STRING {operator} STRING")),
+        },
+        (Object::Boolean(l), Object::Boolean(r)) => match operator {
+            Operator::Eq => Object::Boolean(l == r),
+            Operator::NotEq => Object::Boolean(l != r),
+            _ => Object::Error(format!("unknown operator: BOOLEAN {operator} BOOLEAN")),
+        },
+        (left, right) if left.type_name() != right.type_name() => Object::Error(format!(
+            "type mismatch: {} {operator} {}",
+            left.type_name(),
+            right.type_name()
+        )),
+        (left, right) => Object::Error(format!(
+            "unknown operator: {} {operator} {}",
+            left.type_name(),
+            right.type_name()
+        )),
+    }
+}
+
+fn eval_integer_infix(operator: &Operator, l: i64, r: i64) -> Object {
+    let checked = match operator {
+        Operator::Plus => l.checked_add(r),
+        Operator::Minus => l.checked_sub(r),
+        Operator::Asterisk => l.checked_mul(r),
+        Operator::Slash if r == 0 => return Object::Error("division by zero".to_string()),
+        Operator::Slash => l.checked_div(r),
+        Operator::Lt => return Object::Boolean(l < r),
+        Operator::Gt => return Object::Boolean(l > r),
+        Operator::Eq => return Object::Boolean(l == r),
+        Operator::NotEq => return Object::Boolean(l != r),
+        Operator::Bang => return Object::Error(format!("unknown operator: INTEGER {operator} INTEGER")),
+    };
+
+    match checked {
+        Some(value) => Object::Integer(value),
+        None => Object::Error(format!("integer overflow: {l} {operator} {r}")),
+    }
+}
+
+fn eval_float_infix(operator: &Operator, l: f64, r: f64) -> Object {
+    match operator {
+        Operator::Plus => Object::Float(l + r),
+        Operator::Minus => Object::Float(l - r),
+        Operator::Asterisk => Object::Float(l * r),
+        Operator::Slash => Object::Float(l / r),
+        Operator::Lt => Object::Boolean(l < r),
+        Operator::Gt => Object::Boolean(l > r),
+        Operator::Eq => Object::Boolean(l == r),
+        Operator::NotEq => Object::Boolean(l != r),
+        Operator::Bang => Object::Error(format!("unknown operator: FLOAT {operator} FLOAT")),
+    }
+}
+
+fn eval_if_expression(x: &If, env: &Environment) -> Object {
+    let condition = eval_expression(&x.condition, env);
+    if condition.is_error() {
+        return condition;
+    }
+
+    if condition.is_truthy() {
+        eval_block_statement(&x.consequence, env)
+    } else if let Some(alternative) = &x.alternative {
+        eval_block_statement(alternative, env)
+    } else {
+        Object::Null
+    }
+}
+
+fn eval_call_expression(call: &Call, env: &Environment) -> Object {
+    let function = eval_expression(&call.function, env);
+    if function.is_error() {
+        return function;
+    }
+    let arguments = match eval_expressions(&call.arguments, env) {
+        Ok(arguments) => arguments,
+        Err(err) => return err,
+    };
+
+    apply_function(function, arguments, env)
+}
+
+/// Calls `function` in a new scope enclosed by the one it was defined in, with
+/// each parameter bound to its argument.
+fn apply_function(function: Object, arguments: Vec<Object>, caller: &Environment) -> Object {
+    let Object::Function(function) = function else {
+        return Object::Error(format!("not a function: {}", function.type_name()));
+    };
+    if function.parameters.len() != arguments.len() {
+        return Object::Error(format!(
+            "wrong number of arguments: expected {}, got {}",
+            function.parameters.len(),
+            arguments.len()
+        ));
+    }
+
+    // Recursing without bound would overflow the native stack and abort.
+    if caller.call_depth() >= MAX_CALL_DEPTH {
+        return Object::Error("stack overflow".to_string());
+    }
+
+    let env = Environment::enclosed_for_call(&function.env, caller);
+    for (parameter, argument) in function.parameters.iter().zip(arguments) {
+        env.set(parameter.value.clone(), argument);
+    }
+
+    match eval_block_statement(&function.body, &env) {
+        Object::ReturnValue(value) => *value,
+        result => result,
+    }
+}
+
+fn eval_index_expression(x: &Index, env: &Environment) -> Object {
+    let left = eval_expression(&x.left, env);
+    if left.is_error() {
+        return left;
+    }
+    let index = eval_expression(&x.index, env);
+    if index.is_error() {
+        return index;
+    }
+
+    eval_index(left, index)
+}
+
+/// Looks up `index` in an array or hash. Missing elements are `null`.
+pub(crate) fn eval_index(left: Object, index: Object) -> Object {
+    match (left, index) {
+        (Object::Array(elements), Object::Integer(i)) => usize::try_from(i)
+            .ok()
+            .and_then(|i| elements.into_iter().nth(i))
+            .unwrap_or(Object::Null),
+        (Object::Hash(pairs), index) => match index.hash_key() {
+            Some(key) => pairs.get(&key).cloned().unwrap_or(Object::Null),
+            None => Object::Error(format!("unusable as hash key: {}", index.type_name())),
+        },
+        (left, index) => Object::Error(format!(
+            "index operator not supported: {}[{}]",
+            left.type_name(),
+            index.type_name()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{eval, STACK_SIZE};
+    use crate::environment::Environment;
+    use crate::lexer::Lexer;
+    use crate::object::Object;
+    use crate::parser::Parser;
+
+    fn run(input: &str) -> Object {
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+        eval(&program, &Environment::new())
+    }
+
+    #[test]
+    fn test_eval_integer_expressions() {
+        let tests = [
+            ("5", 5),
+            ("-10", -10),
+            ("--5", 5),
+            ("5 + 5 + 5 + 5 - 10", 10),
+            ("2 * 2 * 2 * 2 * 2", 32),
+            ("-50 + 100 + -50", 0),
+            ("20 + 2 * -10", 0),
+            ("50 / 2 * 2 + 10", 60),
+            ("3 * (3 * 3) + 10", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+            ("0x10 + 0b11", 19),
+            ("-9223372036854775808", i64::MIN),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Object::Integer(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_eval_float_expressions() {
+        let tests = [("1.5", 1.5), ("-2.5", -2.5), ("1.5 * 2", 3.0), ("1 / 4.0", 0.25), ("0.1 + 0.2", 0.1 + 0.2)];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Object::Float(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_eval_boolean_expressions() {
+        let tests = [
+            ("true", true),
+            ("false", false),
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("1 == 1.0", true),
+            ("true == true", true),
+            ("true != false", true),
+            ("(1 < 2) == true", true),
+            ("(1 > 2) == true", false),
+            ("\"a\" == \"a\"", true),
+            ("\"a\" != \"b\"", true),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Object::Boolean(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_bang_operator() {
+        let tests = [("!true", false), ("!false", true), ("!5", false), ("!!true", true), ("!!5", true)];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Object::Boolean(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_if_else_expressions() {
+        let tests = [
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 < 2) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let tests = [
+            ("return 10;", 10),
+            ("return 10; 9;", 10),
+            ("return 2 * 5; 9;", 10),
+            ("9; return 2 * 5; 9;", 10),
+            ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", 10),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Object::Integer(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let tests = [
+            ("let a = 5; a;", 5),
+            ("let a = 5 * 5; a;", 25),
+            ("let a = 5; let b = a; b;", 5),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Object::Integer(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_strings_arrays_and_hashes() {
+        let tests = [
+            ("\"Hello\" + \" \" + \"World!\"", "Hello World!"),
+            ("[1, 2 * 2, 3 + 3]", "[1, 4, 6]"),
+            ("[1, 2, 3][0]", "1"),
+            ("let i = 0; [1][i]", "1"),
+            ("[1, 2, 3][1 + 1]", "3"),
+            ("[1, 2, 3][3]", "null"),
+            ("[1, 2, 3][-1]", "null"),
+            ("{\"one\": 1, true: 2, 3: 3}", "{3: 3, true: 2, \"one\": 1}"),
+            ("{\"1\": 1, 1: 2}", "{1: 2, \"1\": 1}"),
+            ("[\"a\", [\"b\\n\"], {\"k\": \"v\"}]", "[\"a\", [\"b\\n\"], {\"k\": \"v\"}]"),
+            ("[\"a\"][0]", "a"),
+            ("{\"foo\": 5}[\"foo\"]", "5"),
+            ("{\"foo\": 5}[\"bar\"]", "null"),
+            ("{true: 5}[true]", "5"),
+            ("{}[5]", "null"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input).to_string(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_function_application() {
+        let tests = [
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let identity = fn(x) { return x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x; }(5)", 5),
+            ("let f = fn() { return 1; 2 }; f() + 10", 11),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Object::Integer(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_closures() {
+        let tests = [
+            ("let adder = fn(x) { fn(y) { x + y } }; let addTwo = adder(2); addTwo(3);", 5),
+            ("let adder = fn(x) { fn(y) { x + y } }; adder(1)(2) + adder(10)(20);", 33),
+            // Parameters and lets inside a function shadow outer bindings
+            // without touching them.
+            ("let x = 1; let f = fn(x) { x }; f(2) + x;", 3),
+            ("let x = 1; let f = fn() { let x = 10; x }; f() + x;", 11),
+            ("let x = 1; let f = fn(y) { let x = x + y; x }; f(5) * 10 + x;", 61),
+            // Closures see the bindings of their defining scope, not the caller's.
+            ("let x = 1; let get = fn() { x }; let call = fn(x) { get() }; call(100);", 1),
+            (
+                "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(15);",
+                610,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Object::Integer(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        // Test threads get a small stack; give the evaluator what `eval` asks for.
+        let results = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(|| {
+                let countdown = "let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } };";
+                [
+                    run(&format!("{countdown} f(1000)")),
+                    run(&format!("{countdown} f(5000)")),
+                    run("let f = fn() { f() + 1 }; f()"),
+                ]
+                .map(|result| result.to_string())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(results, ["0", "error: stack overflow", "error: stack overflow"]);
+    }
+
+    #[test]
+    fn test_function_object() {
+        assert_eq!(run("fn(x) { x + 2; };").to_string(), "fn(x) { (x + 2) }");
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let tests = [
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("if (10 > 1) { true + false; }", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("if (10 > 1) { if (10 > 1) { return true + false; } return 1; }", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("\"Hello\" - \"World\"", "unknown operator: STRING - STRING"),
+            ("foobar", "identifier not found: foobar"),
+            ("1 / 0", "division by zero"),
+            ("9223372036854775807 + 1", "integer overflow: 9223372036854775807 + 1"),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("{}[[]]", "unusable as hash key: ARRAY"),
+            ("1[0]", "index operator not supported: INTEGER[INTEGER]"),
+            ("[1, foobar, 3]", "identifier not found: foobar"),
+            ("let f = fn(x) { x }; f(1, 2)", "wrong number of arguments: expected 1, got 2"),
+            ("5(1)", "not a function: INTEGER"),
+            ("let f = fn() { let y = 1; }; f(); y", "identifier not found: y"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Object::Error(expected.to_string()), "{input}");
+        }
+    }
+}
@@ -1,9 +1,35 @@
-#[derive(Hash, Debug, PartialEq, Eq)]
+use std::fmt;
+
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum LexError {
+    #[error("unterminated string literal")]
+    UnterminatedString,
+    #[error("unterminated block comment")]
+    UnterminatedComment,
+    #[error("unknown escape sequence `\\{0}`")]
+    UnknownEscape(char),
+    #[error("invalid unicode escape `\\u{0}`")]
+    InvalidUnicodeEscape(String),
+    #[error("numeric literal `{0}` is missing digits")]
+    MissingDigits(String),
+    #[error("invalid digit `{digit}` in numeric literal `{literal}`")]
+    InvalidDigit { literal: String, digit: char },
+    #[error("misplaced `_` in numeric literal `{0}`")]
+    MisplacedSeparator(String),
+}
+
+#[derive(Hash, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum TokenKind {
     Illegal,
+    Error,
+    Comment,
     Eof,
     Ident,
     Int,
+    Float,
+    Str,
     Assign,
     Plus,
     Minus,
@@ -15,11 +41,14 @@ pub enum TokenKind {
     Eq,
     NotEq,
     Comma,
+    Colon,
     Semicolon,
     Lparen,
     Rparen,
     Lbrace,
     Rbrace,
+    Lbracket,
+    Rbracket,
     Function,
     Let,
     True,
@@ -29,12 +58,57 @@ pub enum TokenKind {
     Return,
 }
 
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Illegal => f.write_str("illegal token"),
+            TokenKind::Error => f.write_str("invalid token"),
+            TokenKind::Comment => f.write_str("comment"),
+            TokenKind::Eof => f.write_str("end of input"),
+            TokenKind::Ident => f.write_str("identifier"),
+            TokenKind::Int => f.write_str("integer literal"),
+            TokenKind::Float => f.write_str("float literal"),
+            TokenKind::Str => f.write_str("string literal"),
+            TokenKind::Assign => f.write_str("`=`"),
+            TokenKind::Plus => f.write_str("`+`"),
+            TokenKind::Minus => f.write_str("`-`"),
+            TokenKind::Bang => f.write_str("`!`"),
+            TokenKind::Asterisk => f.write_str("`*`"),
+            TokenKind::Slash => f.write_str("`/`"),
+            TokenKind::Lt => f.write_str("`<`"),
+            TokenKind::Gt => f.write_str("`>`"),
+            TokenKind::Eq => f.write_str("`==`"),
+            TokenKind::NotEq => f.write_str("`!=`"),
+            TokenKind::Comma => f.write_str("`,`"),
+            TokenKind::Colon => f.write_str("`:`"),
+            TokenKind::Semicolon => f.write_str("`;`"),
+            TokenKind::Lparen => f.write_str("`(`"),
+            TokenKind::Rparen => f.write_str("`)`"),
+            TokenKind::Lbrace => f.write_str("`{`"),
+            TokenKind::Rbrace => f.write_str("`}`"),
+            TokenKind::Lbracket => f.write_str("`[`"),
+            TokenKind::Rbracket => f.write_str("`]`"),
+            TokenKind::Function => f.write_str("`fn`"),
+            TokenKind::Let => f.write_str("`let`"),
+            TokenKind::True => f.write_str("`true`"),
+            TokenKind::False => f.write_str("`false`"),
+            TokenKind::If => f.write_str("`if`"),
+            TokenKind::Else => f.write_str("`else`"),
+            TokenKind::Return => f.write_str("`return`"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Token {
     Illegal(String),
+    Error(LexError),
+    Comment(String),
     Eof,
     Ident(String),
     Int(String),
+    Float(String),
+    Str(String),
     Assign,
     Plus,
     Minus,
@@ -46,11 +120,14 @@ pub enum Token {
     Eq,
     NotEq,
     Comma,
+    Colon,
     Semicolon,
     Lparen,
     Rparen,
     Lbrace,
     Rbrace,
+    Lbracket,
+    Rbracket,
     Function,
     Let,
     True,
@@ -68,9 +145,13 @@ impl Token {
     pub fn kind(&self) -> TokenKind {
         match self {
             Token::Illegal(_) => TokenKind::Illegal,
+            Token::Error(_) => TokenKind::Error,
+            Token::Comment(_) => TokenKind::Comment,
             Token::Eof => TokenKind::Eof,
             Token::Ident(_) => TokenKind::Ident,
             Token::Int(_) => TokenKind::Int,
+            Token::Float(_) => TokenKind::Float,
+            Token::Str(_) => TokenKind::Str,
             Token::Assign => TokenKind::Assign,
             Token::Plus => TokenKind::Plus,
             Token::Minus => TokenKind::Minus,
@@ -82,11 +163,14 @@ impl Token {
             Token::Eq => TokenKind::Eq,
             Token::NotEq => TokenKind::NotEq,
             Token::Comma => TokenKind::Comma,
+            Token::Colon => TokenKind::Colon,
             Token::Semicolon => TokenKind::Semicolon,
             Token::Lparen => TokenKind::Lparen,
             Token::Rparen => TokenKind::Rparen,
             Token::Lbrace => TokenKind::Lbrace,
             Token::Rbrace => TokenKind::Rbrace,
+            Token::Lbracket => TokenKind::Lbracket,
+            Token::Rbracket => TokenKind::Rbracket,
             Token::Function => TokenKind::Function,
             Token::Let => TokenKind::Let,
             Token::True => TokenKind::True,
@@ -98,88 +182,186 @@ impl Token {
     }
 }
 
-impl ToString for Token {
-    fn to_string(&self) -> String {
-        let mut result = String::new();
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Token::Illegal(s) => result.push_str(s),
-            Token::Eof => result.push_str("\0"),
-            Token::Ident(s) => result.push_str(s),
-            Token::Int(s) => result.push_str(s),
-            Token::Assign => result.push_str("="),
-            Token::Plus => result.push_str("+"),
-            Token::Minus => result.push_str("-"),
-            Token::Bang => result.push_str("!"),
-            Token::Asterisk => result.push_str("*"),
-            Token::Slash => result.push_str("/"),
-            Token::Lt => result.push_str("<"),
-            Token::Gt => result.push_str(">"),
-            Token::Eq => result.push_str("=="),
-            Token::NotEq => result.push_str("!="),
-            Token::Comma => result.push_str(","),
-            Token::Semicolon => result.push_str(";"),
-            Token::Lparen => result.push_str("("),
-            Token::Rparen => result.push_str(")"),
-            Token::Lbrace => result.push_str("{"),
-            Token::Rbrace => result.push_str("}"),
-            Token::Function => result.push_str("fn"),
-            Token::Let => result.push_str("let"),
-            Token::True => result.push_str("true"),
-            Token::False => result.push_str("false"),
-            Token::If => result.push_str("if"),
-            Token::Else => result.push_str("else"),
-            Token::Return => result.push_str("return"),
-        }
-        result
+            Token::Illegal(s) => f.write_str(s),
+            Token::Error(e) => write!(f, "{e}"),
+            Token::Comment(s) => f.write_str(s),
+            Token::Eof => f.write_str("\0"),
+            Token::Ident(s) => f.write_str(s),
+            Token::Int(s) => f.write_str(s),
+            Token::Float(s) => f.write_str(s),
+            Token::Str(s) => f.write_str(s),
+            Token::Assign => f.write_str("="),
+            Token::Plus => f.write_str("+"),
+            Token::Minus => f.write_str("-"),
+            Token::Bang => f.write_str("!"),
+            Token::Asterisk => f.write_str("*"),
+            Token::Slash => f.write_str("/"),
+            Token::Lt => f.write_str("<"),
+            Token::Gt => f.write_str(">"),
+            Token::Eq => f.write_str("=="),
+            Token::NotEq => f.write_str("!="),
+            Token::Comma => f.write_str(","),
+            Token::Colon => f.write_str(":"),
+            Token::Semicolon => f.write_str(";"),
+            Token::Lparen => f.write_str("("),
+            Token::Rparen => f.write_str(")"),
+            Token::Lbrace => f.write_str("{"),
+            Token::Rbrace => f.write_str("}"),
+            Token::Lbracket => f.write_str("["),
+            Token::Rbracket => f.write_str("]"),
+            Token::Function => f.write_str("fn"),
+            Token::Let => f.write_str("let"),
+            Token::True => f.write_str("true"),
+            Token::False => f.write_str("false"),
+            Token::If => f.write_str("if"),
+            Token::Else => f.write_str("else"),
+            Token::Return => f.write_str("return"),
+        }
+    }
+}
+
+/// Byte range `start..end` of a token in the source, along with the line and
+/// column of its first character. Lines and columns start at 1.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
     }
 }
 
+impl Span {
+    /// Joins `self` with a later span, covering everything from the start of
+    /// `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
 #[derive(Debug)]
 pub struct Lexer {
-    ch: u8,
-    input: Vec<u8>,
+    ch: char,
+    input: Vec<char>,
     position: usize,
     read_position: usize,
+    offset: usize,
+    read_offset: usize,
+    line: usize,
+    column: usize,
+    keep_comments: bool,
 }
 
 impl Iterator for Lexer {
-    type Item = Token;
+    type Item = SpannedToken;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.skip_whitespace();
+        loop {
+            self.skip_whitespace();
+
+            let (start, line, column) = (self.offset, self.line, self.column);
+            let token = self.read_token();
+
+            if let Token::Comment(_) = token {
+                if !self.keep_comments {
+                    continue;
+                }
+            }
+
+            let span = Span {
+                start,
+                end: self.offset,
+                line,
+                column,
+            };
+
+            return Some(SpannedToken { token, span });
+        }
+    }
+}
 
+impl Lexer {
+    pub fn new(input: String) -> Lexer {
+        let mut lex = Lexer {
+            ch: '\0',
+            input: input.chars().collect(),
+            position: 0,
+            read_position: 0,
+            offset: 0,
+            read_offset: 0,
+            line: 1,
+            column: 0,
+            keep_comments: false,
+        };
+        lex.read_char();
+        lex
+    }
+
+    /// Emits comments as `Token::Comment` instead of discarding them, for
+    /// tools such as formatters that need to keep them.
+    pub fn with_comments(mut self) -> Lexer {
+        self.keep_comments = true;
+        self
+    }
+
+    fn read_token(&mut self) -> Token {
         let tok = match self.ch {
-            b'=' => {
-                if self.peek_char() == b'=' {
+            '=' => {
+                if self.peek_char() == '=' {
                     self.read_char();
                     Token::Eq
                 } else {
                     Token::Assign
                 }
             }
-            b'!' => {
-                if self.peek_char() == b'=' {
+            '!' => {
+                if self.peek_char() == '=' {
                     self.read_char();
                     Token::NotEq
                 } else {
                     Token::Bang
                 }
             }
-            b'+' => Token::Plus,
-            b'-' => Token::Minus,
-            b'*' => Token::Asterisk,
-            b'/' => Token::Slash,
-            b'<' => Token::Lt,
-            b'>' => Token::Gt,
-            b',' => Token::Comma,
-            b';' => Token::Semicolon,
-            b'(' => Token::Lparen,
-            b')' => Token::Rparen,
-            b'{' => Token::Lbrace,
-            b'}' => Token::Rbrace,
-            b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Asterisk,
+            '/' => match self.peek_char() {
+                '/' => return self.read_line_comment(),
+                '*' => return self.read_block_comment(),
+                _ => Token::Slash,
+            },
+            '<' => Token::Lt,
+            '>' => Token::Gt,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            ';' => Token::Semicolon,
+            '(' => Token::Lparen,
+            ')' => Token::Rparen,
+            '{' => Token::Lbrace,
+            '}' => Token::Rbrace,
+            '[' => Token::Lbracket,
+            ']' => Token::Rbracket,
+            '"' => self.read_string(),
+            c if is_identifier_start(c) => {
                 let ident = self.read_identifier();
-                return Some(match ident.as_str() {
+                return match ident.as_str() {
                     "if" => Token::If,
                     "fn" => Token::Function,
                     "let" => Token::Let,
@@ -188,80 +370,291 @@ impl Iterator for Lexer {
                     "false" => Token::False,
                     "return" => Token::Return,
                     _ => Token::Ident(ident),
-                });
-            }
-            b'0'..=b'9' => return Some(Token::Int(self.read_integer())),
-            0 => Token::Eof,
-            _ => {
-                let s = String::from_utf8_lossy(&[self.ch]).to_string();
-                Token::Illegal(s)
+                };
             }
+            '0'..='9' => return self.read_number(),
+            '\0' => Token::Eof,
+            c => Token::Illegal(c.to_string()),
         };
         self.read_char();
-        Some(tok)
-    }
-}
-
-impl Lexer {
-    pub fn new(input: String) -> Lexer {
-        let mut lex = Lexer {
-            ch: 0,
-            input: input.into_bytes(),
-            position: 0,
-            read_position: 0,
-        };
-        lex.read_char();
-        lex
+        tok
     }
 
     fn read_char(&mut self) {
+        // Once past the end, stay put so repeated `Eof`s share one span.
+        if self.read_position > self.input.len() {
+            return;
+        }
+
+        if self.ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        self.offset = self.read_offset;
         if self.read_position >= self.input.len() {
-            self.ch = 0;
+            self.ch = '\0';
         } else {
             self.ch = self.input[self.read_position];
+            self.read_offset += self.ch.len_utf8();
         }
         self.position = self.read_position;
         self.read_position += 1;
     }
 
-    fn peek_char(&self) -> u8 {
+    fn peek_char(&self) -> char {
         if self.read_position >= self.input.len() {
-            return 0;
+            '\0'
         } else {
-            return self.input[self.read_position];
+            self.input[self.read_position]
         }
     }
 
+    /// Collects the characters from `start` up to the current one.
+    fn text_from(&self, start: usize) -> String {
+        self.input[start..self.position].iter().collect()
+    }
+
     fn skip_whitespace(&mut self) {
-        while self.ch.is_ascii_whitespace() {
+        while self.ch.is_whitespace() {
             self.read_char();
         }
     }
 
+    /// Reads a `//` comment up to, but not including, the end of the line.
+    fn read_line_comment(&mut self) -> Token {
+        let start = self.position;
+
+        while self.ch != '\n' && self.ch != '\0' {
+            self.read_char();
+        }
+
+        Token::Comment(self.text_from(start))
+    }
+
+    /// Reads a `/* */` comment. Block comments nest, so every `/*` inside one
+    /// needs its own `*/`.
+    fn read_block_comment(&mut self) -> Token {
+        let start = self.position;
+        let mut depth = 0;
+
+        loop {
+            match (self.ch, self.peek_char()) {
+                ('\0', _) => return Token::Error(LexError::UnterminatedComment),
+                ('/', '*') => {
+                    depth += 1;
+                    self.read_char();
+                }
+                ('*', '/') => {
+                    depth -= 1;
+                    self.read_char();
+
+                    if depth == 0 {
+                        self.read_char();
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            self.read_char();
+        }
+
+        Token::Comment(self.text_from(start))
+    }
+
     fn read_identifier(&mut self) -> String {
         let current_position = self.position;
 
-        while self.ch.is_ascii_alphabetic() || self.ch == b'_' {
+        while is_identifier_continue(self.ch) {
             self.read_char();
         }
 
-        String::from_utf8_lossy(&self.input[current_position..self.position]).to_string()
+        self.text_from(current_position)
     }
 
-    fn read_integer(&mut self) -> String {
-        let current_position = self.position;
+    /// Reads a double-quoted string, leaving `ch` on the closing quote. A bad
+    /// escape doesn't stop the scan, so the rest of the literal is still
+    /// consumed and lexing resumes after it.
+    fn read_string(&mut self) -> Token {
+        let mut value = String::new();
+        let mut error = None;
+
+        loop {
+            self.read_char();
+
+            match self.ch {
+                '"' => break,
+                '\0' => return Token::Error(LexError::UnterminatedString),
+                '\\' => {
+                    self.read_char();
+
+                    match self.read_escape() {
+                        Ok(c) => value.push(c),
+                        Err(LexError::UnterminatedString) => {
+                            return Token::Error(LexError::UnterminatedString)
+                        }
+                        Err(e) => {
+                            error.get_or_insert(e);
+                        }
+                    }
+                }
+                c => value.push(c),
+            }
+        }
+
+        match error {
+            Some(e) => Token::Error(e),
+            None => Token::Str(value),
+        }
+    }
+
+    fn read_escape(&mut self) -> Result<char, LexError> {
+        match self.ch {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            'u' => self.read_unicode_escape(),
+            '\0' => Err(LexError::UnterminatedString),
+            c => Err(LexError::UnknownEscape(c)),
+        }
+    }
+
+    /// Reads the `{XXXX}` part of a `\u{XXXX}` escape.
+    fn read_unicode_escape(&mut self) -> Result<char, LexError> {
+        if self.peek_char() != '{' {
+            return Err(LexError::InvalidUnicodeEscape(String::new()));
+        }
+        self.read_char();
 
-        while self.ch.is_ascii_digit() {
+        let mut digits = String::new();
+        while self.peek_char().is_ascii_hexdigit() {
             self.read_char();
+            digits.push(self.ch);
+        }
+
+        if self.peek_char() != '}' {
+            return Err(LexError::InvalidUnicodeEscape(format!("{{{digits}")));
         }
+        self.read_char();
 
-        String::from_utf8_lossy(&self.input[current_position..self.position]).to_string()
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| (1..=6).contains(&digits.len()))
+            .and_then(char::from_u32)
+            .ok_or_else(|| LexError::InvalidUnicodeEscape(format!("{{{digits}}}")))
+    }
+
+    /// Reads an integer or float literal, keeping its source text so the
+    /// parser can report it as written. Integers may use a `0x`, `0o` or `0b`
+    /// prefix, and any digits may be separated with `_`.
+    fn read_number(&mut self) -> Token {
+        let start = self.position;
+
+        let radix = match (self.ch, self.peek_char()) {
+            ('0', 'x') => Some(16),
+            ('0', 'o') => Some(8),
+            ('0', 'b') => Some(2),
+            _ => None,
+        };
+
+        if let Some(radix) = radix {
+            self.read_char();
+            self.read_char();
+
+            while is_identifier_continue(self.ch) {
+                self.read_char();
+            }
+
+            let literal = self.text_from(start);
+            let digits = &literal[2..];
+
+            if let Some(digit) = digits.chars().find(|c| *c != '_' && !c.is_digit(radix)) {
+                return Token::Error(LexError::InvalidDigit { literal, digit });
+            }
+            if !digits.chars().any(|c| c != '_') {
+                return Token::Error(LexError::MissingDigits(literal));
+            }
+            if has_misplaced_separator(digits) {
+                return Token::Error(LexError::MisplacedSeparator(literal));
+            }
+            return Token::Int(literal);
+        }
+
+        self.read_digits();
+        let mut is_float = false;
+        let mut missing_exponent = false;
+
+        if self.ch == '.' && self.peek_char().is_ascii_digit() {
+            is_float = true;
+            self.read_char();
+            self.read_digits();
+        }
+
+        if matches!(self.ch, 'e' | 'E') {
+            is_float = true;
+            self.read_char();
+
+            if matches!(self.ch, '+' | '-') {
+                self.read_char();
+            }
+
+            let exponent = self.position;
+            self.read_digits();
+            missing_exponent = !self.input[exponent..self.position].iter().any(char::is_ascii_digit);
+        }
+
+        // A literal runs into any identifier characters that follow it, so
+        // `123abc` is one bad literal rather than a number and a name.
+        let suffix = self.ch;
+        while is_identifier_continue(self.ch) {
+            self.read_char();
+        }
+
+        let literal = self.text_from(start);
+        if is_identifier_continue(suffix) {
+            return Token::Error(LexError::InvalidDigit { literal, digit: suffix });
+        }
+        if missing_exponent {
+            return Token::Error(LexError::MissingDigits(literal));
+        }
+        if literal.split(['.', 'e', 'E', '+', '-']).any(has_misplaced_separator) {
+            return Token::Error(LexError::MisplacedSeparator(literal));
+        }
+        if is_float {
+            Token::Float(literal)
+        } else {
+            Token::Int(literal)
+        }
+    }
+
+    fn read_digits(&mut self) {
+        while self.ch.is_ascii_digit() || self.ch == '_' {
+            self.read_char();
+        }
     }
 }
 
+/// Identifiers follow Unicode's XID rules, with `_` also allowed up front.
+fn is_identifier_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
+/// A `_` separator must sit between two digits, so it can't start or end a run
+/// of digits after a radix prefix, a `.`, or an exponent marker and sign.
+fn has_misplaced_separator(digits: &str) -> bool {
+    digits.starts_with('_') || digits.ends_with('_') || digits.contains("__")
+}
+
 #[cfg(test)]
 mod test {
-    use super::{Lexer, Token};
+    use super::{LexError, Lexer, Span, Token};
 
     #[test]
     fn get_next_token() {
@@ -274,7 +667,7 @@ mod test {
             }; 
 
             let result = add(five, ten);
-            !-/*5;
+            !-/ *5;
             5 < 10 > 5;
 
             if (5 < 10) {
@@ -285,6 +678,8 @@ mod test {
 
             10 == 10;
             10 != 9;
+            [1, 2][0];
+            {\"a\": 1};
         ";
 
         let tokens = vec![
@@ -361,13 +756,261 @@ mod test {
             Token::NotEq,
             Token::Int("9".into()),
             Token::Semicolon,
+            Token::Lbracket,
+            Token::Int("1".into()),
+            Token::Comma,
+            Token::Int("2".into()),
+            Token::Rbracket,
+            Token::Lbracket,
+            Token::Int("0".into()),
+            Token::Rbracket,
+            Token::Semicolon,
+            Token::Lbrace,
+            Token::Str("a".into()),
+            Token::Colon,
+            Token::Int("1".into()),
+            Token::Rbrace,
+            Token::Semicolon,
             Token::Eof,
         ];
 
         let mut l = Lexer::new(input.into());
 
         for token in tokens {
+            let lex_token = l.next().unwrap().token;
+            println!("expected: {token:?} received: {lex_token:?}");
+            assert_eq!(token, lex_token);
+        }
+    }
+
+    #[test]
+    fn token_spans() {
+        let input = "let x = 5;\n  x + 10;";
+
+        let spans = vec![
+            (Token::Let, 0, 3, 1, 1),
+            (Token::Ident("x".into()), 4, 5, 1, 5),
+            (Token::Assign, 6, 7, 1, 7),
+            (Token::Int("5".into()), 8, 9, 1, 9),
+            (Token::Semicolon, 9, 10, 1, 10),
+            (Token::Ident("x".into()), 13, 14, 2, 3),
+            (Token::Plus, 15, 16, 2, 5),
+            (Token::Int("10".into()), 17, 19, 2, 7),
+            (Token::Semicolon, 19, 20, 2, 9),
+            (Token::Eof, 20, 20, 2, 10),
+            (Token::Eof, 20, 20, 2, 10),
+        ];
+
+        let mut l = Lexer::new(input.into());
+
+        for (token, start, end, line, column) in spans {
             let lex_token = l.next().unwrap();
+            assert_eq!(lex_token.token, token);
+            assert_eq!(lex_token.span, Span { start, end, line, column });
+        }
+    }
+
+    #[test]
+    fn string_literals() {
+        let input = r#"
+            "foobar"
+            "foo bar"
+            ""
+            "line\nbreak\ttab \"quoted\" back\\slash"
+            "\u{48}\u{1F600} ñ"
+            "bad \q escape" "ok"
+            "bad \u{110000}"
+            "bad \u{zz}"
+            "unterminated
+        "#;
+
+        let tokens = vec![
+            Token::Str("foobar".into()),
+            Token::Str("foo bar".into()),
+            Token::Str("".into()),
+            Token::Str("line\nbreak\ttab \"quoted\" back\\slash".into()),
+            Token::Str("H\u{1F600} ñ".into()),
+            Token::Error(LexError::UnknownEscape('q')),
+            Token::Str("ok".into()),
+            Token::Error(LexError::InvalidUnicodeEscape("{110000}".into())),
+            Token::Error(LexError::InvalidUnicodeEscape("{".into())),
+            Token::Error(LexError::UnterminatedString),
+            Token::Eof,
+        ];
+
+        let mut l = Lexer::new(input.into());
+
+        for token in tokens {
+            let lex_token = l.next().unwrap().token;
+            println!("expected: {token:?} received: {lex_token:?}");
+            assert_eq!(token, lex_token);
+        }
+    }
+
+    #[test]
+    fn comments() {
+        let input = "
+            let x = 5; // trailing comment
+            // a whole line
+            x / 2 /* inline */ + 1
+            /* outer /* nested */ still outer */ y
+            /// doc-like
+            /* open
+        ";
+
+        let tokens = vec![
+            Token::Let,
+            Token::Ident("x".into()),
+            Token::Assign,
+            Token::Int("5".into()),
+            Token::Semicolon,
+            Token::Ident("x".into()),
+            Token::Slash,
+            Token::Int("2".into()),
+            Token::Plus,
+            Token::Int("1".into()),
+            Token::Ident("y".into()),
+            Token::Error(LexError::UnterminatedComment),
+            Token::Eof,
+        ];
+
+        let mut l = Lexer::new(input.into());
+
+        for token in tokens {
+            let lex_token = l.next().unwrap().token;
+            println!("expected: {token:?} received: {lex_token:?}");
+            assert_eq!(token, lex_token);
+        }
+    }
+
+    #[test]
+    fn comments_as_trivia() {
+        let input = "a // one\n/* two /* three */ */ b";
+
+        let tokens = vec![
+            (Token::Ident("a".into()), 0, 1),
+            (Token::Comment("// one".into()), 2, 8),
+            (Token::Comment("/* two /* three */ */".into()), 9, 30),
+            (Token::Ident("b".into()), 31, 32),
+            (Token::Eof, 32, 32),
+        ];
+
+        let mut l = Lexer::new(input.into()).with_comments();
+
+        for (token, start, end) in tokens {
+            let lex_token = l.next().unwrap();
+            assert_eq!(lex_token.token, token);
+            assert_eq!((lex_token.span.start, lex_token.span.end), (start, end));
+        }
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        let input = "let x1 = ñandú_2 + 名前; _tmp9 café→";
+
+        let tokens = vec![
+            (Token::Let, 0, 3, 1),
+            (Token::Ident("x1".into()), 4, 6, 5),
+            (Token::Assign, 7, 8, 8),
+            (Token::Ident("ñandú_2".into()), 9, 18, 10),
+            (Token::Plus, 19, 20, 18),
+            (Token::Ident("名前".into()), 21, 27, 20),
+            (Token::Semicolon, 27, 28, 22),
+            (Token::Ident("_tmp9".into()), 29, 34, 24),
+            (Token::Ident("café".into()), 35, 40, 30),
+            (Token::Illegal("→".into()), 40, 43, 34),
+            (Token::Eof, 43, 43, 35),
+        ];
+
+        let mut l = Lexer::new(input.into());
+
+        for (token, start, end, column) in tokens {
+            let lex_token = l.next().unwrap();
+            assert_eq!(lex_token.token, token);
+            assert_eq!(lex_token.span, Span { start, end, line: 1, column });
+        }
+    }
+
+    #[test]
+    fn digits_do_not_start_identifiers() {
+        let input = "x1 _9 1x";
+
+        let tokens = vec![
+            Token::Ident("x1".into()),
+            Token::Ident("_9".into()),
+            Token::Error(LexError::InvalidDigit { literal: "1x".into(), digit: 'x' }),
+            Token::Eof,
+        ];
+
+        let mut l = Lexer::new(input.into());
+
+        for token in tokens {
+            assert_eq!(l.next().unwrap().token, token);
+        }
+    }
+
+    #[test]
+    fn numeric_literals() {
+        let input = "
+            0 42 1_000_000 007
+            0xff 0xDEAD_beef 0o17 0b1010
+            3.14 1e-9 2.5E+3 1_0.0_1 6e2
+            1.foo 1.
+            0x 0b102 0o8 0xfg 1e 2e+ 3.5e_
+            9_ 1__0 0xf_ 1_.5 2e1_ 0b1__0
+            0b_1 0x_ff 1e_5 1e+_5 2.5E-_1
+            123abc 1.5x 1ex 7é 0b1z
+        ";
+
+        let tokens = vec![
+            Token::Int("0".into()),
+            Token::Int("42".into()),
+            Token::Int("1_000_000".into()),
+            Token::Int("007".into()),
+            Token::Int("0xff".into()),
+            Token::Int("0xDEAD_beef".into()),
+            Token::Int("0o17".into()),
+            Token::Int("0b1010".into()),
+            Token::Float("3.14".into()),
+            Token::Float("1e-9".into()),
+            Token::Float("2.5E+3".into()),
+            Token::Float("1_0.0_1".into()),
+            Token::Float("6e2".into()),
+            Token::Int("1".into()),
+            Token::Illegal(".".into()),
+            Token::Ident("foo".into()),
+            Token::Int("1".into()),
+            Token::Illegal(".".into()),
+            Token::Error(LexError::MissingDigits("0x".into())),
+            Token::Error(LexError::InvalidDigit { literal: "0b102".into(), digit: '2' }),
+            Token::Error(LexError::InvalidDigit { literal: "0o8".into(), digit: '8' }),
+            Token::Error(LexError::InvalidDigit { literal: "0xfg".into(), digit: 'g' }),
+            Token::Error(LexError::MissingDigits("1e".into())),
+            Token::Error(LexError::MissingDigits("2e+".into())),
+            Token::Error(LexError::MissingDigits("3.5e_".into())),
+            Token::Error(LexError::MisplacedSeparator("9_".into())),
+            Token::Error(LexError::MisplacedSeparator("1__0".into())),
+            Token::Error(LexError::MisplacedSeparator("0xf_".into())),
+            Token::Error(LexError::MisplacedSeparator("1_.5".into())),
+            Token::Error(LexError::MisplacedSeparator("2e1_".into())),
+            Token::Error(LexError::MisplacedSeparator("0b1__0".into())),
+            Token::Error(LexError::MisplacedSeparator("0b_1".into())),
+            Token::Error(LexError::MisplacedSeparator("0x_ff".into())),
+            Token::Error(LexError::MisplacedSeparator("1e_5".into())),
+            Token::Error(LexError::MisplacedSeparator("1e+_5".into())),
+            Token::Error(LexError::MisplacedSeparator("2.5E-_1".into())),
+            Token::Error(LexError::InvalidDigit { literal: "123abc".into(), digit: 'a' }),
+            Token::Error(LexError::InvalidDigit { literal: "1.5x".into(), digit: 'x' }),
+            Token::Error(LexError::InvalidDigit { literal: "1ex".into(), digit: 'x' }),
+            Token::Error(LexError::InvalidDigit { literal: "7é".into(), digit: 'é' }),
+            Token::Error(LexError::InvalidDigit { literal: "0b1z".into(), digit: 'z' }),
+            Token::Eof,
+        ];
+
+        let mut l = Lexer::new(input.into());
+
+        for token in tokens {
+            let lex_token = l.next().unwrap().token;
             println!("expected: {token:?} received: {lex_token:?}");
             assert_eq!(token, lex_token);
         }
@@ -1,3 +1,11 @@
 pub mod ast;
+pub mod code;
+pub mod compiler;
+pub mod diagnostics;
+pub mod environment;
+pub mod evaluator;
 pub mod lexer;
+pub mod object;
 pub mod parser;
+pub mod serialize;
+pub mod vm;
@@ -1,17 +1,43 @@
-use monkey::lexer::{Lexer, Token};
+use monkey::ast::{Program, Statement};
+use monkey::code::disassemble;
+use monkey::compiler::Compiler;
+use monkey::diagnostics::{render_parse_errors, Diagnostic};
+use monkey::environment::Environment;
+use monkey::evaluator::{self, eval};
+use monkey::lexer::Lexer;
+use monkey::object::Object;
+use monkey::parser::Parser;
+use monkey::serialize::{read_bytecode, write_bytecode, MAGIC};
+use monkey::vm::Vm;
 
-use std::io::{self, Write};
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::process;
+use std::thread;
 
 const PROMPT_STRING: &str = ">>> ";
 
+const USAGE: &str = "\
+usage: monkey                          start the REPL
+       monkey <file>                   run a script or compiled program
+       monkey --compile <file> <out>   compile a script to bytecode\
+";
+
 const WELCOME_TEXT: &str = "\
 Welcome user! This is the monkey programming language!!\n\
-Feel free to type in commands\
+Feel free to type in commands, or `:disasm <code>` to see the bytecode for some code\
+";
+
+const DISASM_USAGE: &str = "\
+usage: :disasm <code>\n\
+<code> is compiled on its own, without the bindings made earlier in the session, \
+so names it does not define show up as fresh global slots\
 ";
 
 fn prompt() -> String {
     let mut input = String::new();
-    
+
     print!("{}", PROMPT_STRING);
     io::stdout().flush().unwrap();
     io::stdin().read_line(&mut input).unwrap();
@@ -19,20 +45,143 @@ fn prompt() -> String {
     input.trim().to_string()
 }
 
+/// What the REPL echoes after evaluating `program`. Errors are always shown,
+/// but a line that ends by successfully binding a `let` has nothing to show.
+fn repl_output(program: &Program, result: &Object) -> Option<String> {
+    let ends_with_let = matches!(program.statements.last(), Some(Statement::Let(_)) | None);
+    if ends_with_let && !result.is_error() {
+        return None;
+    }
+    Some(result.to_string())
+}
+
+fn run_file(path: &str) -> io::Result<()> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(&MAGIC) {
+        run_bytecode(path, &bytes);
+        return Ok(());
+    }
+    let source = String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let mut p = Parser::new(Lexer::new(source.clone()));
+    let program = match p.parse_program() {
+        Ok(program) => program,
+        Err(errors) => {
+            let color = io::stderr().is_terminal();
+            eprint!("{}", render_parse_errors(&errors, path, &source, color));
+            process::exit(1);
+        }
+    };
+
+    match eval(&program, &Environment::new()) {
+        Object::Null => {}
+        err @ Object::Error(_) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+        result => println!("{result}"),
+    }
+
+    Ok(())
+}
+
+fn run_bytecode(path: &str, bytes: &[u8]) {
+    let bytecode = match read_bytecode(bytes) {
+        Ok(bytecode) => bytecode,
+        Err(err) => {
+            eprintln!("error: cannot load {path}: {err}");
+            process::exit(1);
+        }
+    };
+
+    match Vm::new(&bytecode).run() {
+        Ok(Object::Null) => {}
+        Ok(result) => println!("{result}"),
+        Err(err) => {
+            eprintln!("error: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+/// Compiles the script at `path` and writes the bytecode, with line tables,
+/// to `output`.
+fn compile_file(path: &str, output: &str) -> io::Result<()> {
+    let source = fs::read_to_string(path)?;
+    let color = io::stderr().is_terminal();
+
+    let mut p = Parser::new(Lexer::new(source.clone()));
+    let program = match p.parse_program() {
+        Ok(program) => program,
+        Err(errors) => {
+            eprint!("{}", render_parse_errors(&errors, path, &source, color));
+            process::exit(1);
+        }
+    };
+
+    match Compiler::new().compile(&program) {
+        Ok(bytecode) => fs::write(output, write_bytecode(&bytecode, true)),
+        Err(err) => {
+            eprint!("{}", Diagnostic::from(&err).render(path, &source, color));
+            process::exit(1);
+        }
+    }
+}
+
+/// Prints the bytecode listing for `source`, compiled on its own.
+fn print_disassembly(source: &str, color: bool) {
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let program = match p.parse_program() {
+        Ok(program) => program,
+        Err(errors) => return print!("{}", render_parse_errors(&errors, "<repl>", source, color)),
+    };
+
+    match Compiler::new().compile(&program) {
+        Ok(bytecode) => print!("{}", disassemble(&bytecode)),
+        Err(err) => print!("{}", Diagnostic::from(&err).render("<repl>", source, color)),
+    }
+}
+
 fn main() -> io::Result<()> {
+    thread::Builder::new()
+        .stack_size(evaluator::STACK_SIZE)
+        .spawn(run)?
+        .join()
+        .unwrap_or_else(|_| process::exit(101))
+}
+
+fn run() -> io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => {}
+        [flag, path, output] if flag == "--compile" => return compile_file(path, output),
+        [path] if !path.starts_with("--") => return run_file(path),
+        _ => {
+            eprintln!("{USAGE}");
+            process::exit(2);
+        }
+    }
+
     println!("{}", WELCOME_TEXT);
+    let color = io::stdout().is_terminal();
+    let env = Environment::new();
     loop {
         let input = prompt();
-        
+
         match input.as_str() {
             ":exit" => break,
+            ":disasm" => println!("{}", DISASM_USAGE),
+            _ if input.starts_with(":disasm ") => print_disassembly(&input[":disasm ".len()..], color),
             _ => {
-                let l = Lexer::new(input);
-                for token in l {
-                    if token == Token::Eof {
-                        break;
-                    }                    
-                    println!("Token: {token:?}");
+                let mut p = Parser::new(Lexer::new(input.clone()));
+                match p.parse_program() {
+                    Ok(program) => {
+                        let result = eval(&program, &env);
+                        if let Some(output) = repl_output(&program, &result) {
+                            println!("{output}");
+                        }
+                    }
+                    Err(errors) => print!("{}", render_parse_errors(&errors, "<repl>", &input, color)),
                 }
             }
         }
@@ -40,3 +189,32 @@ fn main() -> io::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::repl_output;
+    use monkey::environment::Environment;
+    use monkey::evaluator::eval;
+    use monkey::lexer::Lexer;
+    use monkey::parser::Parser;
+
+    #[test]
+    fn test_repl_output() {
+        let env = Environment::new();
+        let tests = [
+            ("let x = 5;", None),
+            ("x * 2", Some("10")),
+            ("let y = foo;", Some("error: identifier not found: foo")),
+            ("let y = 1 + true;", Some("error: type mismatch: INTEGER + BOOLEAN")),
+            ("y", Some("error: identifier not found: y")),
+            ("", None),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+            let result = eval(&program, &env);
+            assert_eq!(repl_output(&program, &result).as_deref(), expected, "{input}");
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{BlockStatement, Ident, Quoted};
+use crate::code::CompiledFunction;
+use crate::environment::Environment;
+
+/// A runtime value produced by evaluating an expression.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Object {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    Array(Vec<Object>),
+    Hash(BTreeMap<HashKey, Object>),
+    Function(Rc<Function>),
+    Closure(Rc<Closure>),
+    Null,
+    /// Wraps the value of a `return` so it can unwind through enclosing blocks.
+    ReturnValue(Box<Object>),
+    Error(String),
+}
+
+/// A function value together with the environment it was defined in.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Function {
+    pub parameters: Vec<Ident>,
+    pub body: BlockStatement,
+    pub env: Environment,
+}
+
+/// A compiled function together with the free variables it captured.
+#[derive(Debug, PartialEq)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Object>,
+}
+
+/// The subset of objects that can be used as hash keys.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl Object {
+    /// The name of the object's type, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Function(_) | Object::Closure(_) => "FUNCTION",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Error(_) => "ERROR",
+        }
+    }
+
+    /// Only `false` and `null` are falsy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Boolean(false) | Object::Null)
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(x) => Some(HashKey::Integer(*x)),
+            Object::Boolean(x) => Some(HashKey::Boolean(*x)),
+            Object::String(x) => Some(HashKey::String(x.clone())),
+            _ => None,
+        }
+    }
+
+    /// Strings inside arrays and hashes are quoted so that `["1"]` and `[1]`
+    /// print differently.
+    fn to_nested_string(&self) -> String {
+        match self {
+            Object::String(x) => Quoted(x).to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Object {
+        match key {
+            HashKey::Integer(x) => Object::Integer(x),
+            HashKey::Boolean(x) => Object::Boolean(x),
+            HashKey::String(x) => Object::String(x),
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Integer(x) => write!(f, "{x}"),
+            Object::Float(x) => write!(f, "{x:?}"),
+            Object::Boolean(x) => write!(f, "{x}"),
+            Object::String(x) => f.write_str(x),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(Object::to_nested_string).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> =
+                    pairs.iter().map(|(k, v)| format!("{k}: {}", v.to_nested_string())).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::Function(function) => {
+                let parameters: Vec<String> = function.parameters.iter().map(|p| p.to_string()).collect();
+                write!(f, "fn({}) {{ {} }}", parameters.join(", "), function.body)
+            }
+            Object::Closure(_) => f.write_str("<compiled function>"),
+            Object::Null => f.write_str("null"),
+            Object::ReturnValue(x) => write!(f, "{x}"),
+            Object::Error(message) => write!(f, "error: {message}"),
+        }
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashKey::Integer(x) => write!(f, "{x}"),
+            HashKey::Boolean(x) => write!(f, "{x}"),
+            HashKey::String(x) => write!(f, "{}", Quoted(x)),
+        }
+    }
+}
@@ -1,17 +1,63 @@
 use std::mem;
 use std::collections::HashMap;
 
+use thiserror::Error;
+
 use crate::ast::*;
-use crate::lexer::{Lexer, Token, TokenKind};
+use crate::lexer::{LexError, Lexer, Span, Token, TokenKind};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("expected {expected}, found {found}")]
+    UnexpectedToken { expected: TokenKind, found: TokenKind, span: Span },
+    #[error("expected expression, found {found}")]
+    NoPrefixParser { found: TokenKind, span: Span },
+    #[error("illegal character `{literal}`")]
+    IllegalToken { literal: String, span: Span },
+    #[error("integer literal `{literal}` is out of range")]
+    IntegerOverflow { literal: String, span: Span },
+    #[error("float literal `{literal}` is out of range")]
+    FloatOverflow { literal: String, span: Span },
+    #[error("{error}")]
+    Lex { error: LexError, span: Span },
+    #[error("expression is nested too deeply; the limit is {limit}")]
+    TooDeep { limit: usize, span: Span },
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::NoPrefixParser { span, .. }
+            | ParseError::IllegalToken { span, .. }
+            | ParseError::IntegerOverflow { span, .. }
+            | ParseError::FloatOverflow { span, .. }
+            | ParseError::Lex { span, .. }
+            | ParseError::TooDeep { span, .. } => *span,
+        }
+    }
+}
+
+/// How deeply expressions may nest. Parsing, evaluating and compiling all
+/// recurse once per level, and this keeps them within a default 2 MiB thread
+/// stack even in debug builds.
+pub const MAX_NESTING_DEPTH: usize = 64;
 
 type InfixParser = fn(&mut Parser, Expression) -> Option<Expression>;
-type PrefixParser = fn(&mut Parser) -> Expression;
+type PrefixParser = fn(&mut Parser) -> Option<Expression>;
 
 #[derive(Debug)]
 pub struct Parser {
     lexer: Lexer,
     peek_token: Token,
     current_token: Token,
+    peek_span: Span,
+    current_span: Span,
+    errors: Vec<ParseError>,
+    brace_depth: usize,
+    blocks: Vec<usize>,
+    block_closed: bool,
+    depth: usize,
     infix_parsers: HashMap<TokenKind, InfixParser>,
     prefix_parsers: HashMap<TokenKind, PrefixParser>,
 }
@@ -22,19 +68,60 @@ impl Parser {
             lexer: l,
             peek_token: Token::Eof,
             current_token: Token::Eof,
+            peek_span: Span::default(),
+            current_span: Span::default(),
+            errors: Vec::new(),
+            brace_depth: 0,
+            blocks: Vec::new(),
+            block_closed: false,
+            depth: 0,
             infix_parsers: HashMap::new(),
             prefix_parsers: HashMap::new(),
         };
         
         p.register_prefix(TokenKind::Int, Parser::parse_integer_literal);
+        p.register_prefix(TokenKind::Float, Parser::parse_float_literal);
         p.register_prefix(TokenKind::Ident, Parser::parse_identifier);
-        
+        p.register_prefix(TokenKind::True, Parser::parse_boolean);
+        p.register_prefix(TokenKind::False, Parser::parse_boolean);
+        p.register_prefix(TokenKind::Str, Parser::parse_string_literal);
+        p.register_prefix(TokenKind::Bang, Parser::parse_prefix_expression);
+        p.register_prefix(TokenKind::Minus, Parser::parse_prefix_expression);
+        p.register_prefix(TokenKind::Lparen, Parser::parse_grouped_expression);
+        p.register_prefix(TokenKind::If, Parser::parse_if_expression);
+        p.register_prefix(TokenKind::Function, Parser::parse_function_literal);
+        p.register_prefix(TokenKind::Lbracket, Parser::parse_array_literal);
+        p.register_prefix(TokenKind::Lbrace, Parser::parse_hash_literal);
+
+        p.register_infix(TokenKind::Plus, Parser::parse_infix_expression);
+        p.register_infix(TokenKind::Minus, Parser::parse_infix_expression);
+        p.register_infix(TokenKind::Asterisk, Parser::parse_infix_expression);
+        p.register_infix(TokenKind::Slash, Parser::parse_infix_expression);
+        p.register_infix(TokenKind::Lt, Parser::parse_infix_expression);
+        p.register_infix(TokenKind::Gt, Parser::parse_infix_expression);
+        p.register_infix(TokenKind::Eq, Parser::parse_infix_expression);
+        p.register_infix(TokenKind::NotEq, Parser::parse_infix_expression);
+        p.register_infix(TokenKind::Lparen, Parser::parse_call_expression);
+        p.register_infix(TokenKind::Lbracket, Parser::parse_index_expression);
+
         p.next_token();
         p.next_token();
         p
     }
 
-    pub fn parse_program(&mut self) -> Option<Program> {
+    pub fn parse_program(&mut self) -> Result<Program, Vec<ParseError>> {
+        let p = self.parse_statements();
+
+        if self.errors.is_empty() {
+            Ok(p)
+        } else {
+            Err(mem::take(&mut self.errors))
+        }
+    }
+
+    /// Parses to the end of input, keeping every statement that survives
+    /// error recovery.
+    fn parse_statements(&mut self) -> Program {
         let mut p = Program {
             statements: Vec::new(),
         };
@@ -46,98 +133,473 @@ impl Parser {
             self.next_token();
         }
 
-        Some(p)
+        p
     }
 
     fn parse_statement(&mut self) -> Option<Statement> {
-        match self.current_token {
+        let errors = self.errors.len();
+
+        let stmt = match self.current_token {
             Token::Let => {
                 self
                 .parse_let_statement()
-                .map(|x| Statement::Let(x))
+                .map(Statement::Let)
             }
             Token::Return => {
                 self
                 .parse_return_statement()
-                .map(|x| Statement::Return(x))
+                .map(Statement::Return)
             }
+            Token::Semicolon => None,
             _ => {
                 self
                 .parse_expression_statement()
-                .map(|x| Statement::ExpressionStatement(x))
+                .map(Statement::ExpressionStatement)
+            }
+        };
+
+        if stmt.is_none() && self.errors.len() > errors {
+            self.synchronize();
+        }
+
+        stmt
+    }
+
+    /// Skips the rest of a statement that failed to parse, so the next call to
+    /// `next_token` lands on something a statement can start from. Stops after
+    /// a `;`, or before a statement keyword or the `}` closing the enclosing
+    /// block. Anything inside braces opened along the way is skipped.
+    fn synchronize(&mut self) {
+        let depth = self.blocks.last().copied().unwrap_or(0);
+
+        // The statement broke on the `}` closing its block, which has already
+        // been consumed; tell the block to end here.
+        if self.brace_depth < depth {
+            self.block_closed = true;
+            return;
+        }
+
+        loop {
+            let at_depth = self.brace_depth == depth;
+
+            if at_depth && self.is_token(TokenKind::Semicolon) {
+                return;
+            }
+
+            match self.peek_token.kind() {
+                TokenKind::Eof => return,
+                TokenKind::Rbrace if at_depth && !self.blocks.is_empty() => return,
+                TokenKind::Let | TokenKind::Return | TokenKind::Function | TokenKind::If
+                    if at_depth => return,
+                _ => {}
             }
+
+            self.next_token();
         }
     }
 
     fn parse_let_statement(&mut self) -> Option<Let> {
+        let start = self.current_span;
+
         if !self.expect(TokenKind::Ident) {
             return None;
         }
 
-        let name = Ident {
-            value: self.current_token.to_string(),
-        };
+        let name = self.current_ident();
 
         if !self.expect(TokenKind::Assign) {
             return None;
         }
 
-        let value = Expression::Ident(name.clone());
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
 
-        while !self.is_token(TokenKind::Semicolon) {
+        if self.is_peek_token(TokenKind::Semicolon) {
             self.next_token();
         }
 
-        Some(Let { name, value })
+        Some(Let { name, value, span: start.to(self.current_span) })
     }
 
     fn parse_return_statement(&mut self) -> Option<Return> {
+        let start = self.current_span;
         self.next_token();
 
-        let value = Expression::Ident(Ident { value: " ".into() });
+        let value = self.parse_expression(Precedence::Lowest)?;
 
-        while !self.is_token(TokenKind::Semicolon) {
+        if self.is_peek_token(TokenKind::Semicolon) {
             self.next_token();
         }
 
-        Some(Return { value })
+        Some(Return { value, span: start.to(self.current_span) })
     }
 
     fn parse_expression_statement(&mut self) -> Option<ExpressionStatement> {
-        let Some(value) = self.parse_expression(Precedence::Lowest) else {
-            return None;
-        };
-        
+        let start = self.current_span;
+        let value = self.parse_expression(Precedence::Lowest)?;
+
         if self.is_peek_token(TokenKind::Semicolon) {
             self.next_token();
         }
 
-        Some(ExpressionStatement { value })        
+        Some(ExpressionStatement { value, span: start.to(self.current_span) })
     }
 
     fn parse_expression(&mut self, p: Precedence) -> Option<Expression> {
-        let prefix = self.prefix_parsers.get(&self.current_token.kind());
+        if self.depth >= MAX_NESTING_DEPTH {
+            self.errors.push(ParseError::TooDeep {
+                limit: MAX_NESTING_DEPTH,
+                span: self.current_span,
+            });
+            return None;
+        }
+
+        self.depth += 1;
+        let expression = self.parse_operators(p);
+        self.depth -= 1;
+        expression
+    }
+
+    fn parse_operators(&mut self, p: Precedence) -> Option<Expression> {
+        let Some(&prefix) = self.prefix_parsers.get(&self.current_token.kind()) else {
+            self.no_prefix_parser_error();
+            return None;
+        };
+        let mut left = prefix(self)?;
+
+        while !self.is_peek_token(TokenKind::Semicolon) && p < self.peek_precedence() {
+            let Some(&infix) = self.infix_parsers.get(&self.peek_token.kind()) else {
+                return Some(left);
+            };
+
+            self.next_token();
+            left = infix(self, left)?;
+        }
+
+        Some(left)
+    }
+
+    fn parse_identifier(&mut self) -> Option<Expression> {
+        Some(Expression::Ident(self.current_ident()))
+    }
+
+    fn parse_integer_literal(&mut self) -> Option<Expression> {
+        self.parse_integer(i64::MAX.unsigned_abs())
+    }
+
+    /// Parses the current integer token, reporting it if it is above `max`.
+    fn parse_integer(&mut self, max: u64) -> Option<Expression> {
+        let literal = self.current_token.to_string();
+        let digits: String = literal.chars().filter(|&c| c != '_').collect();
+
+        let (digits, radix) = match digits.get(..2) {
+            Some("0x") => (&digits[2..], 16),
+            Some("0o") => (&digits[2..], 8),
+            Some("0b") => (&digits[2..], 2),
+            _ => (&digits[..], 10),
+        };
+
+        let Some(value) = u64::from_str_radix(digits, radix).ok().filter(|&value| value <= max) else {
+            self.errors.push(ParseError::IntegerOverflow {
+                literal,
+                span: self.current_span,
+            });
+            return None;
+        };
+
+        Some(Expression::IntegerLiteral(IntegerLiteral {
+            value,
+            span: self.current_span,
+        }))
+    }
+
+    fn parse_float_literal(&mut self) -> Option<Expression> {
+        let literal = self.current_token.to_string();
+        let digits: String = literal.chars().filter(|&c| c != '_').collect();
+
+        let value = match digits.parse::<f64>() {
+            Ok(value) if value.is_finite() => value,
+            _ => {
+                self.errors.push(ParseError::FloatOverflow {
+                    literal,
+                    span: self.current_span,
+                });
+                return None;
+            }
+        };
+
+        Some(Expression::FloatLiteral(FloatLiteral {
+            value,
+            span: self.current_span,
+        }))
+    }
+
+    fn parse_boolean(&mut self) -> Option<Expression> {
+        Some(Expression::Boolean(Boolean {
+            value: self.is_token(TokenKind::True),
+            span: self.current_span,
+        }))
+    }
+
+    fn parse_string_literal(&mut self) -> Option<Expression> {
+        let Token::Str(ref value) = self.current_token else {
+            return None;
+        };
+
+        Some(Expression::StringLiteral(StringLiteral {
+            value: value.clone(),
+            span: self.current_span,
+        }))
+    }
+
+    fn parse_array_literal(&mut self) -> Option<Expression> {
+        let start = self.current_span;
+        let elements = self.parse_expression_list(TokenKind::Rbracket)?;
+
+        Some(Expression::ArrayLiteral(ArrayLiteral {
+            elements,
+            span: start.to(self.current_span),
+        }))
+    }
+
+    /// Parses `{key: value, ...}`. A `{` only reaches here in expression
+    /// position; braces after `if`, `else` and `fn(...)` are block statements
+    /// and are parsed by `parse_block_statement` instead.
+    fn parse_hash_literal(&mut self) -> Option<Expression> {
+        let start = self.current_span;
+        let mut pairs = Vec::new();
+
+        while !self.is_peek_token(TokenKind::Rbrace) {
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
+
+            if !self.expect(TokenKind::Colon) {
+                return None;
+            }
+
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+
+            pairs.push((key, value));
+
+            if !self.is_peek_token(TokenKind::Rbrace) && !self.expect(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        self.next_token();
+
+        Some(Expression::HashLiteral(HashLiteral {
+            pairs,
+            span: start.to(self.current_span),
+        }))
+    }
+
+    fn parse_prefix_expression(&mut self) -> Option<Expression> {
+        let start = self.current_span;
+        let operator = match self.current_token {
+            Token::Bang => Operator::Bang,
+            Token::Minus => Operator::Minus,
+            _ => return None,
+        };
+
+        self.next_token();
+
+        // `i64::MIN` has no positive counterpart, so its literal is only in
+        // range as the whole operand of a `-`.
+        let right = if operator == Operator::Minus
+            && self.is_token(TokenKind::Int)
+            && self.peek_precedence() <= Precedence::Prefix
+        {
+            self.parse_integer(i64::MIN.unsigned_abs())?
+        } else {
+            self.parse_expression(Precedence::Prefix)?
+        };
+
+        Some(Expression::Prefix(Prefix {
+            operator,
+            right: Box::new(right),
+            span: start.to(self.current_span),
+        }))
+    }
+
+    fn parse_grouped_expression(&mut self) -> Option<Expression> {
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect(TokenKind::Rparen) {
+            return None;
+        }
+
+        Some(value)
+    }
+
+    fn parse_if_expression(&mut self) -> Option<Expression> {
+        let start = self.current_span;
+
+        if !self.expect(TokenKind::Lparen) {
+            return None;
+        }
+
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect(TokenKind::Rparen) || !self.expect(TokenKind::Lbrace) {
+            return None;
+        }
 
-        if let Some(f) = prefix {
-            Some(f(self))
+        let consequence = self.parse_block_statement()?;
+
+        let alternative = if self.is_peek_token(TokenKind::Else) {
+            self.next_token();
+
+            if !self.expect(TokenKind::Lbrace) {
+                return None;
+            }
+
+            Some(self.parse_block_statement()?)
         } else {
             None
+        };
+
+        Some(Expression::If(If {
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+            span: start.to(self.current_span),
+        }))
+    }
+
+    fn parse_block_statement(&mut self) -> Option<BlockStatement> {
+        let start = self.current_span;
+        let mut statements = Vec::new();
+
+        self.blocks.push(self.brace_depth);
+        self.next_token();
+
+        while !self.is_token(TokenKind::Rbrace) {
+            if self.is_token(TokenKind::Eof) {
+                self.errors.push(ParseError::UnexpectedToken {
+                    expected: TokenKind::Rbrace,
+                    found: TokenKind::Eof,
+                    span: self.current_span,
+                });
+                self.blocks.pop();
+                return None;
+            }
+
+            if let Some(x) = self.parse_statement() {
+                statements.push(x);
+            }
+
+            if mem::take(&mut self.block_closed) {
+                break;
+            }
+            self.next_token();
         }
+
+        self.blocks.pop();
+
+        Some(BlockStatement { statements, span: start.to(self.current_span) })
     }
 
-    fn parse_identifier(&mut self) -> Expression {
-        Expression::Ident(Ident { value: self.current_token.to_string() })        
+    fn parse_function_literal(&mut self) -> Option<Expression> {
+        let start = self.current_span;
+
+        if !self.expect(TokenKind::Lparen) {
+            return None;
+        }
+
+        let parameters = self.parse_function_parameters()?;
+
+        if !self.expect(TokenKind::Lbrace) {
+            return None;
+        }
+
+        let body = self.parse_block_statement()?;
+
+        Some(Expression::FunctionLiteral(FunctionLiteral {
+            parameters,
+            body,
+            span: start.to(self.current_span),
+        }))
     }
 
-    fn parse_integer_literal(&mut self) -> Expression {
-        let value = self.current_token.to_string().parse::<u64>().unwrap();
-        
-        Expression::IntegerLiteral(IntegerLiteral { value })
+    fn parse_function_parameters(&mut self) -> Option<Vec<Ident>> {
+        let mut parameters = Vec::new();
+
+        if self.is_peek_token(TokenKind::Rparen) {
+            self.next_token();
+            return Some(parameters);
+        }
+
+        if !self.expect(TokenKind::Ident) {
+            return None;
+        }
+        parameters.push(self.current_ident());
+
+        while self.is_peek_token(TokenKind::Comma) {
+            self.next_token();
+            if self.is_peek_token(TokenKind::Rparen) {
+                break;
+            }
+
+            if !self.expect(TokenKind::Ident) {
+                return None;
+            }
+            parameters.push(self.current_ident());
+        }
+
+        if !self.expect(TokenKind::Rparen) {
+            return None;
+        }
+
+        Some(parameters)
+    }
+
+    fn parse_infix_expression(&mut self, left: Expression) -> Option<Expression> {
+        let operator = infix_operator(&self.current_token)?;
+        let precedence = self.current_precedence();
+
+        self.next_token();
+        let right = self.parse_expression(precedence)?;
+
+        Some(Expression::Infix(Infix {
+            span: left.span().to(self.current_span),
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        }))
     }
 
     fn next_token(&mut self) {
         self.current_token = mem::replace(&mut self.peek_token, Token::Eof);
-        self.peek_token = self.lexer.next().unwrap();
+        self.current_span = self.peek_span;
+
+        match self.current_token {
+            Token::Lbrace => self.brace_depth += 1,
+            Token::Rbrace => self.brace_depth = self.brace_depth.saturating_sub(1),
+            _ => {}
+        }
+
+        // Comments only show up when the lexer was asked to keep them, and
+        // they carry no meaning here.
+        let next = loop {
+            let next = self.lexer.next().unwrap();
+            if next.token.kind() != TokenKind::Comment {
+                break next;
+            }
+        };
+        self.peek_token = next.token;
+        self.peek_span = next.span;
+    }
+
+    fn current_ident(&self) -> Ident {
+        Ident {
+            value: self.current_token.to_string(),
+            span: self.current_span,
+        }
     }
 
     fn is_token(&self, tok: TokenKind) -> bool {
@@ -148,15 +610,95 @@ impl Parser {
         self.peek_token.kind() == tok
     }
 
+    fn parse_call_expression(&mut self, function: Expression) -> Option<Expression> {
+        let arguments = self.parse_expression_list(TokenKind::Rparen)?;
+
+        Some(Expression::Call(Call {
+            span: function.span().to(self.current_span),
+            function: Box::new(function),
+            arguments,
+        }))
+    }
+
+    fn parse_index_expression(&mut self, left: Expression) -> Option<Expression> {
+        self.next_token();
+        let index = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect(TokenKind::Rbracket) {
+            return None;
+        }
+
+        Some(Expression::Index(Index {
+            span: left.span().to(self.current_span),
+            left: Box::new(left),
+            index: Box::new(index),
+        }))
+    }
+
+    fn parse_expression_list(&mut self, end: TokenKind) -> Option<Vec<Expression>> {
+        let mut list = Vec::new();
+
+        if self.is_peek_token(end) {
+            self.next_token();
+            return Some(list);
+        }
+
+        self.next_token();
+        list.push(self.parse_expression(Precedence::Lowest)?);
+
+        while self.is_peek_token(TokenKind::Comma) {
+            self.next_token();
+            if self.is_peek_token(end) {
+                break;
+            }
+
+            self.next_token();
+            list.push(self.parse_expression(Precedence::Lowest)?);
+        }
+
+        if !self.expect(end) {
+            return None;
+        }
+
+        Some(list)
+    }
+
+    fn peek_precedence(&self) -> Precedence {
+        precedence_of(&self.peek_token)
+    }
+
+    fn current_precedence(&self) -> Precedence {
+        precedence_of(&self.current_token)
+    }
+
     fn expect(&mut self, tok: TokenKind) -> bool {
         if self.is_peek_token(tok) {
             self.next_token();
             true
         } else {
+            self.peek_error(tok);
             false
         }
     }
 
+    fn peek_error(&mut self, tok: TokenKind) {
+        self.errors.push(ParseError::UnexpectedToken {
+            expected: tok,
+            found: self.peek_token.kind(),
+            span: self.peek_span,
+        });
+    }
+
+    fn no_prefix_parser_error(&mut self) {
+        let span = self.current_span;
+        let err = match &self.current_token {
+            Token::Illegal(s) => ParseError::IllegalToken { literal: s.clone(), span },
+            Token::Error(e) => ParseError::Lex { error: e.clone(), span },
+            tok => ParseError::NoPrefixParser { found: tok.kind(), span },
+        };
+        self.errors.push(err);
+    }
+
     fn register_infix(&mut self, tok: TokenKind, p: InfixParser) {
         self.infix_parsers.insert(tok, p);
     }
@@ -166,26 +708,52 @@ impl Parser {
     }
 }
 
+fn precedence_of(tok: &Token) -> Precedence {
+    match tok {
+        Token::Eq | Token::NotEq => Precedence::Equals,
+        Token::Lt | Token::Gt => Precedence::LessGreater,
+        Token::Plus | Token::Minus => Precedence::Sum,
+        Token::Asterisk | Token::Slash => Precedence::Product,
+        Token::Lparen => Precedence::Call,
+        Token::Lbracket => Precedence::Index,
+        _ => Precedence::Lowest,
+    }
+}
+
+fn infix_operator(tok: &Token) -> Option<Operator> {
+    match tok {
+        Token::Plus => Some(Operator::Plus),
+        Token::Minus => Some(Operator::Minus),
+        Token::Asterisk => Some(Operator::Asterisk),
+        Token::Slash => Some(Operator::Slash),
+        Token::Lt => Some(Operator::Lt),
+        Token::Gt => Some(Operator::Gt),
+        Token::Eq => Some(Operator::Eq),
+        Token::NotEq => Some(Operator::NotEq),
+        _ => None,
+    }
+}
 
 #[cfg(test)]
 mod test {
-    use super::Parser;
+    use super::{Parser, ParseError, MAX_NESTING_DEPTH};
     use crate::ast::{
         Statement,
-        Expression, Let, Ident,
+        Expression, Operator,
     };
-    use crate::lexer::Lexer;
+    use crate::lexer::{LexError, Lexer, Span, TokenKind};
 
     #[test]
     fn test_let_statements() {
         let input = "
             let x = 5;
-            let y = 10;
+            let y = 10 * x;
     
-            let foobar = 838383;
+            let foobar = -y + 838383
         ";
 
-        let identifiers = vec!["x", "y", "foobar"];
+        let identifiers = ["x", "y", "foobar"];
+        let values = ["5", "(10 * x)", "((-y) + 838383)"];
 
         let mut p = Parser::new(Lexer::new(input.into()));
         let program = p.parse_program().expect("parser should produce valid ast");
@@ -199,6 +767,7 @@ mod test {
                 panic!("expected let statement. Found {stmt:?}");
             };
             assert_eq!(s.name.value, identifiers[i]);
+            assert_eq!(s.value.to_string(), values[i]);
         }
     }
 
@@ -206,21 +775,24 @@ mod test {
     fn test_return_statements() {
         let input = "
             return 5;
-            return 10;
-            return 993322;
+            return 10 + x;
+            return !993322
         ";
 
+        let values = ["5", "(10 + x)", "(!993322)"];
+
         let mut p = Parser::new(Lexer::new(input.into()));
         let program = p.parse_program().expect("parser should produce valid ast.");
 
         assert_eq!(program.statements.len(), 3);
 
-        for stmt in program.statements.iter() {
+        for (i, stmt) in program.statements.iter().enumerate() {
             println!("{stmt:?}");
             
-            let Statement::Return(_) = stmt else {
+            let Statement::Return(s) = stmt else {
                 panic!("expected return statement. Found {stmt:?}");
             };
+            assert_eq!(s.value.to_string(), values[i]);
         }
     }
 
@@ -231,7 +803,7 @@ mod test {
             input
         ";
         
-        let identifiers = vec!["foobar", "input"];
+        let identifiers = ["foobar", "input"];
 
         let mut _l = Lexer::new(input.into());
         let mut _p = Parser::new(_l);
@@ -262,7 +834,7 @@ mod test {
             75;
         ";
         
-        let numbers = vec![65, 4, 75];
+        let numbers = [65, 4, 75];
 
         let mut p = Parser::new(Lexer::new(input.into()));
         let program = p.parse_program().expect("parser should produce valid ast");
@@ -283,4 +855,954 @@ mod test {
             assert_eq!(x.value, numbers[i]);
         }
     }
+
+    #[test]
+    fn test_boolean_expressions() {
+        let input = "
+            true;
+            false
+            let ok = true;
+            !false;
+        ";
+
+        let expected = ["true", "false", "let ok = true;", "(!false)"];
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+
+        assert_eq!(program.statements.len(), expected.len());
+
+        for (i, stmt) in program.statements.iter().enumerate() {
+            println!("{stmt:?}");
+            assert_eq!(stmt.to_string(), expected[i]);
+        }
+
+        let Statement::ExpressionStatement(ref s) = program.statements[0] else {
+            panic!("expected expression statement. Found {:?}", program.statements[0]);
+        };
+
+        let Expression::Boolean(ref x) = s.value else {
+            panic!("expected boolean. found {s:?}");
+        };
+
+        assert!(x.value);
+    }
+
+    #[test]
+    fn test_prefix_expressions() {
+        let tests = vec![
+            ("!5;", Operator::Bang, 5),
+            ("-15;", Operator::Minus, 15),
+        ];
+
+        for (input, operator, value) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.statements.len(), 1);
+
+            let Statement::ExpressionStatement(ref s) = program.statements[0] else {
+                panic!("expected expression statement. Found {:?}", program.statements[0]);
+            };
+
+            let Expression::Prefix(ref x) = s.value else {
+                panic!("expected prefix expression. found {s:?}");
+            };
+
+            let Expression::IntegerLiteral(ref r) = *x.right else {
+                panic!("expected integer literal. found {:?}", x.right);
+            };
+
+            assert_eq!(x.operator, operator);
+            assert_eq!(r.value, value);
+        }
+    }
+
+    #[test]
+    fn test_nested_prefix_expressions() {
+        let tests = vec![
+            ("!-x", "(!(-x))"),
+            ("--5", "(-(-5))"),
+            ("!!x", "(!(!x))"),
+            ("-!-5", "(-(!(-5)))"),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.statements.len(), 1);
+            assert_eq!(program.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_infix_expressions() {
+        let tests = vec![
+            ("5 + 5;", 5, Operator::Plus, 5),
+            ("5 - 5;", 5, Operator::Minus, 5),
+            ("5 * 5;", 5, Operator::Asterisk, 5),
+            ("5 / 5;", 5, Operator::Slash, 5),
+            ("5 > 5;", 5, Operator::Gt, 5),
+            ("5 < 5;", 5, Operator::Lt, 5),
+            ("5 == 5;", 5, Operator::Eq, 5),
+            ("5 != 5;", 5, Operator::NotEq, 5),
+        ];
+
+        for (input, left, operator, right) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.statements.len(), 1);
+
+            let Statement::ExpressionStatement(ref s) = program.statements[0] else {
+                panic!("expected expression statement. Found {:?}", program.statements[0]);
+            };
+
+            let Expression::Infix(ref x) = s.value else {
+                panic!("expected infix expression. found {s:?}");
+            };
+
+            let Expression::IntegerLiteral(ref l) = *x.left else {
+                panic!("expected integer literal. found {:?}", x.left);
+            };
+            let Expression::IntegerLiteral(ref r) = *x.right else {
+                panic!("expected integer literal. found {:?}", x.right);
+            };
+
+            assert_eq!(l.value, left);
+            assert_eq!(x.operator, operator);
+            assert_eq!(r.value, right);
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        let tests = vec![
+            ("-a * b", "((-a) * b)"),
+            ("!-a", "(!(-a))"),
+            ("a + b + c", "((a + b) + c)"),
+            ("3 > 5 == false", "((3 > 5) == false)"),
+            ("3 < 5 == true", "((3 < 5) == true)"),
+            ("a + b - c", "((a + b) - c)"),
+            ("a * b * c", "((a * b) * c)"),
+            ("a * b / c", "((a * b) / c)"),
+            ("a + b / c", "(a + (b / c))"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+            ("3 + 4; -5 * 5", "(3 + 4)((-5) * 5)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))"),
+            ("3 + 4 * 5 == 3 * 1 + 4 * 5", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))"),
+            ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
+            ("(5 + 5) * 2", "((5 + 5) * 2)"),
+            ("2 / (5 + 5)", "(2 / (5 + 5))"),
+            ("-(5 + 5)", "(-(5 + 5))"),
+            ("!(true == true)", "(!(true == true))"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            (
+                "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))",
+                "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))",
+            ),
+            ("add(a + b + c * d / f + g)", "add((((a + b) + ((c * d) / f)) + g))"),
+            ("-add(x)", "(-add(x))"),
+            ("a * [1, 2, 3, 4][b * c] * d", "((a * ([1, 2, 3, 4][(b * c)])) * d)"),
+            ("add(a * b[2], b[1], 2 * [1, 2][1])", "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))"),
+            ("-a[0]", "(-(a[0]))"),
+            ("f(x)[0](y)", "(f(x)[0])(y)"),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_grouped_expression_missing_rparen() {
+        let mut p = Parser::new(Lexer::new("(1 + 2 * 3;".into()));
+        let errors = p.parse_program().expect_err("parser should fail");
+
+        assert_eq!(
+            errors,
+            [ParseError::UnexpectedToken {
+                expected: TokenKind::Rparen,
+                found: TokenKind::Semicolon,
+                span: Span { start: 10, end: 11, line: 1, column: 11 },
+            }]
+        );
+        assert_eq!(errors[0].to_string(), "expected `)`, found `;`");
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let tests = vec![
+            ("if (x < y) { x }", "if (x < y) { x }"),
+            ("if (x < y) { x } else { y }", "if (x < y) { x } else { y }"),
+            (
+                "if (x) { let z = x; z } else { return y; }",
+                "if x { let z = x;z } else { return y; }",
+            ),
+            ("let max = if (a > b) { a } else { b };", "let max = if (a > b) { a } else { b };"),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.statements.len(), 1);
+            assert_eq!(program.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_if_expression_branches() {
+        let input = "if (x < y) { x } else { y; z }";
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+
+        let Statement::ExpressionStatement(ref s) = program.statements[0] else {
+            panic!("expected expression statement. Found {:?}", program.statements[0]);
+        };
+
+        let Expression::If(ref x) = s.value else {
+            panic!("expected if expression. found {s:?}");
+        };
+
+        assert_eq!(x.condition.to_string(), "(x < y)");
+        assert_eq!(x.consequence.statements.len(), 1);
+
+        let alternative = x.alternative.as_ref().expect("expected else branch");
+        assert_eq!(alternative.statements.len(), 2);
+    }
+
+    #[test]
+    fn test_if_expression_unclosed_block() {
+        let mut p = Parser::new(Lexer::new("if (x) { x".into()));
+        let errors = p.parse_program().expect_err("parser should fail");
+
+        assert_eq!(
+            errors,
+            [ParseError::UnexpectedToken {
+                expected: TokenKind::Rbrace,
+                found: TokenKind::Eof,
+                span: Span { start: 10, end: 10, line: 1, column: 11 },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_function_literals() {
+        let tests = vec![
+            ("fn() {};", vec![], ""),
+            ("fn(x) { x };", vec!["x"], "x"),
+            ("fn(x, y, z) { x + y * z; }", vec!["x", "y", "z"], "(x + (y * z))"),
+        ];
+
+        for (input, parameters, body) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.statements.len(), 1);
+
+            let Statement::ExpressionStatement(ref s) = program.statements[0] else {
+                panic!("expected expression statement. Found {:?}", program.statements[0]);
+            };
+
+            let Expression::FunctionLiteral(ref x) = s.value else {
+                panic!("expected function literal. found {s:?}");
+            };
+
+            let names: Vec<&str> = x.parameters.iter().map(|p| p.value.as_str()).collect();
+            assert_eq!(names, parameters);
+            assert_eq!(x.body.to_string(), body);
+        }
+    }
+
+    #[test]
+    fn test_malformed_function_parameters() {
+        let tests = vec![
+            ("fn(x,,) {}", "expected identifier, found `,`"),
+            ("fn(1) {}", "expected identifier, found integer literal"),
+            ("fn(x y) {}", "expected `)`, found identifier"),
+            ("fn(x) x", "expected `{`, found identifier"),
+        ];
+
+        for (input, error) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let errors = p.parse_program().expect_err("parser should fail");
+
+            assert_eq!(errors[0].to_string(), error, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_call_expressions() {
+        let tests = vec![
+            ("add(1, 2 * 3, 4 + 5);", "add", vec!["1", "(2 * 3)", "(4 + 5)"]),
+            ("noop();", "noop", vec![]),
+            ("fn(x) { x }(5)", "fn(x) { x }", vec!["5"]),
+            ("adder(1)(2)", "adder(1)", vec!["2"]),
+        ];
+
+        for (input, function, arguments) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.statements.len(), 1);
+
+            let Statement::ExpressionStatement(ref s) = program.statements[0] else {
+                panic!("expected expression statement. Found {:?}", program.statements[0]);
+            };
+
+            let Expression::Call(ref x) = s.value else {
+                panic!("expected call expression. found {s:?}");
+            };
+
+            let args: Vec<String> = x.arguments.iter().map(|a| a.to_string()).collect();
+            assert_eq!(x.function.to_string(), function);
+            assert_eq!(args, arguments);
+        }
+    }
+
+    #[test]
+    fn test_unclosed_call_arguments() {
+        let mut p = Parser::new(Lexer::new("add(1, 2;".into()));
+        let errors = p.parse_program().expect_err("parser should fail");
+
+        assert_eq!(
+            errors,
+            [ParseError::UnexpectedToken {
+                expected: TokenKind::Rparen,
+                found: TokenKind::Semicolon,
+                span: Span { start: 8, end: 9, line: 1, column: 9 },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let tests = vec![
+            ("let = 5;", ParseError::UnexpectedToken {
+                expected: TokenKind::Ident,
+                found: TokenKind::Assign,
+                span: Span { start: 4, end: 5, line: 1, column: 5 },
+            }),
+            ("let x 5;", ParseError::UnexpectedToken {
+                expected: TokenKind::Assign,
+                found: TokenKind::Int,
+                span: Span { start: 6, end: 7, line: 1, column: 7 },
+            }),
+            ("return;", ParseError::NoPrefixParser {
+                found: TokenKind::Semicolon,
+                span: Span { start: 6, end: 7, line: 1, column: 7 },
+            }),
+            ("1 + @", ParseError::IllegalToken {
+                literal: "@".into(),
+                span: Span { start: 4, end: 5, line: 1, column: 5 },
+            }),
+        ];
+
+        for (input, error) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let errors = p.parse_program().expect_err("parser should fail");
+
+            assert_eq!(errors[0], error, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_are_collected() {
+        let input = "
+            let x 5;
+            let y = 10;
+            let = 1;
+        ";
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let errors = p.parse_program().expect_err("parser should fail");
+
+        assert_eq!(
+            errors,
+            [
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::Assign,
+                    found: TokenKind::Int,
+                    span: Span { start: 19, end: 20, line: 2, column: 19 },
+                },
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::Ident,
+                    found: TokenKind::Assign,
+                    span: Span { start: 62, end: 63, line: 4, column: 17 },
+                },
+            ]
+        );
+        assert_eq!(errors[0].to_string(), "expected `=`, found integer literal");
+        assert_eq!(errors[1].to_string(), "expected identifier, found `=`");
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        let input = "
+let x = 9223372036854775807;
+let y = 1 + 99999999999999999999999;
+let z = -9223372036854775808;
+0xffff_ffff_ffff_ffff;
+9223372036854775808;
+-9223372036854775809;
+--9223372036854775808;
+-9223372036854775808[0];";
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let errors = p.parse_program().expect_err("parser should fail");
+
+        assert_eq!(
+            errors,
+            [
+                ParseError::IntegerOverflow {
+                    literal: "99999999999999999999999".into(),
+                    span: Span { start: 42, end: 65, line: 3, column: 13 },
+                },
+                ParseError::IntegerOverflow {
+                    literal: "0xffff_ffff_ffff_ffff".into(),
+                    span: Span { start: 97, end: 118, line: 5, column: 1 },
+                },
+                ParseError::IntegerOverflow {
+                    literal: "9223372036854775808".into(),
+                    span: Span { start: 120, end: 139, line: 6, column: 1 },
+                },
+                ParseError::IntegerOverflow {
+                    literal: "9223372036854775809".into(),
+                    span: Span { start: 142, end: 161, line: 7, column: 2 },
+                },
+                ParseError::IntegerOverflow {
+                    literal: "9223372036854775808".into(),
+                    span: Span { start: 187, end: 206, line: 9, column: 2 },
+                },
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "integer literal `99999999999999999999999` is out of range"
+        );
+    }
+
+    #[test]
+    fn test_node_spans() {
+        let input = "let x = -a + f(1, 2);\nif (x) { x } else { y }";
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+
+        let Statement::Let(ref s) = program.statements[0] else {
+            panic!("expected let statement. Found {:?}", program.statements[0]);
+        };
+        assert_eq!(s.span, Span { start: 0, end: 21, line: 1, column: 1 });
+        assert_eq!(s.name.span, Span { start: 4, end: 5, line: 1, column: 5 });
+
+        let Expression::Infix(ref x) = s.value else {
+            panic!("expected infix expression. found {:?}", s.value);
+        };
+        assert_eq!(x.span, Span { start: 8, end: 20, line: 1, column: 9 });
+        assert_eq!(x.left.span(), Span { start: 8, end: 10, line: 1, column: 9 });
+        assert_eq!(x.right.span(), Span { start: 13, end: 20, line: 1, column: 14 });
+
+        let Expression::Call(ref c) = *x.right else {
+            panic!("expected call expression. found {:?}", x.right);
+        };
+        assert_eq!(c.arguments[1].span(), Span { start: 18, end: 19, line: 1, column: 19 });
+
+        let stmt = &program.statements[1];
+        assert_eq!(stmt.span(), Span { start: 22, end: 45, line: 2, column: 1 });
+
+        let Statement::ExpressionStatement(ref s) = stmt else {
+            panic!("expected expression statement. Found {stmt:?}");
+        };
+        let Expression::If(ref x) = s.value else {
+            panic!("expected if expression. found {:?}", s.value);
+        };
+        assert_eq!(x.consequence.span, Span { start: 29, end: 34, line: 2, column: 8 });
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let input = "
+            let x 5;
+            let y = 10;
+            let = 3;
+            let f = fn(a,,) { let b = a; b };
+            let g = fn(a) { let = 1; a * 2 };
+            if (x) { return ; y } else { @ }
+            add(1, 2
+            let z = 1
+        ";
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let errors = p.parse_program().expect_err("parser should fail");
+
+        let found: Vec<(String, usize)> = errors
+            .iter()
+            .map(|e| (e.to_string(), e.span().line))
+            .collect();
+
+        assert_eq!(
+            found,
+            [
+                ("expected `=`, found integer literal".to_string(), 2),
+                ("expected identifier, found `=`".to_string(), 4),
+                ("expected identifier, found `,`".to_string(), 5),
+                ("expected identifier, found `=`".to_string(), 6),
+                ("expected expression, found `;`".to_string(), 7),
+                ("illegal character `@`".to_string(), 7),
+                ("expected `)`, found `let`".to_string(), 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recovery_keeps_valid_statements() {
+        let tests = vec![
+            (
+                "let a = 1; let b = ; let c = 3; if (a) { a + } c",
+                vec!["let a = 1;", "let c = 3;", "if a {  }", "c"],
+                vec![
+                    ParseError::NoPrefixParser {
+                        found: TokenKind::Semicolon,
+                        span: Span { start: 19, end: 20, line: 1, column: 20 },
+                    },
+                    ParseError::NoPrefixParser {
+                        found: TokenKind::Rbrace,
+                        span: Span { start: 45, end: 46, line: 1, column: 46 },
+                    },
+                ],
+                vec!["expected expression, found `;`", "expected expression, found `}`"],
+            ),
+            (
+                "if (a) { f(fn() {} 1); b } c",
+                vec!["if a { b }", "c"],
+                vec![ParseError::UnexpectedToken {
+                    expected: TokenKind::Rparen,
+                    found: TokenKind::Int,
+                    span: Span { start: 19, end: 20, line: 1, column: 20 },
+                }],
+                vec!["expected `)`, found integer literal"],
+            ),
+        ];
+
+        for (input, statements, errors, messages) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_statements();
+
+            let kept: Vec<String> = program.statements.iter().map(ToString::to_string).collect();
+            assert_eq!(kept, statements, "{input}");
+            assert_eq!(p.errors, errors, "{input}");
+
+            let rendered: Vec<String> = p.errors.iter().map(ToString::to_string).collect();
+            assert_eq!(rendered, messages, "{input}");
+        }
+
+        let input = "let a = 1; let c = fn(x) { x }; if (a) { a } c";
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+        assert_eq!(program.statements.len(), 4);
+    }
+
+    #[test]
+    fn test_unterminated_input_does_not_hang() {
+        let tests = vec![
+            ("let x = 5", "let x = 5;"),
+            ("return 5", "return 5;"),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+            assert_eq!(program.to_string(), expected);
+        }
+
+        let tests = vec![
+            (
+                "let x =",
+                ParseError::NoPrefixParser {
+                    found: TokenKind::Eof,
+                    span: Span { start: 7, end: 7, line: 1, column: 8 },
+                },
+            ),
+            (
+                "return",
+                ParseError::NoPrefixParser {
+                    found: TokenKind::Eof,
+                    span: Span { start: 6, end: 6, line: 1, column: 7 },
+                },
+            ),
+            (
+                "fn(x",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::Rparen,
+                    found: TokenKind::Eof,
+                    span: Span { start: 4, end: 4, line: 1, column: 5 },
+                },
+            ),
+            (
+                "if (x) {",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::Rbrace,
+                    found: TokenKind::Eof,
+                    span: Span { start: 8, end: 8, line: 1, column: 9 },
+                },
+            ),
+            (
+                "(((1",
+                ParseError::UnexpectedToken {
+                    expected: TokenKind::Rparen,
+                    found: TokenKind::Eof,
+                    span: Span { start: 4, end: 4, line: 1, column: 5 },
+                },
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let errors = p.parse_program().expect_err("parser should fail");
+            assert_eq!(errors, [expected], "{input}");
+        }
+    }
+
+    #[test]
+    fn test_string_literals() {
+        let input = r#"let greeting = "hello\t\"world\"\n"; "a" + "b""#;
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+
+        let Statement::Let(ref s) = program.statements[0] else {
+            panic!("expected let statement. Found {:?}", program.statements[0]);
+        };
+
+        let Expression::StringLiteral(ref x) = s.value else {
+            panic!("expected string literal. found {:?}", s.value);
+        };
+
+        assert_eq!(x.value, "hello\t\"world\"\n");
+        assert_eq!(x.span, Span { start: 15, end: 35, line: 1, column: 16 });
+        assert_eq!(program.to_string(), r#"let greeting = "hello\t\"world\"\n";("a" + "b")"#);
+    }
+
+    #[test]
+    fn test_string_literal_errors() {
+        let input = "let a = \"bad \\q\"; let b = \"open";
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let errors = p.parse_program().expect_err("parser should fail");
+
+        assert_eq!(
+            errors,
+            [
+                ParseError::Lex {
+                    error: LexError::UnknownEscape('q'),
+                    span: Span { start: 8, end: 16, line: 1, column: 9 },
+                },
+                ParseError::Lex {
+                    error: LexError::UnterminatedString,
+                    span: Span { start: 26, end: 31, line: 1, column: 27 },
+                },
+            ]
+        );
+        assert_eq!(errors[0].to_string(), "unknown escape sequence `\\q`");
+    }
+
+    #[test]
+    fn test_comments_are_ignored() {
+        let input = "
+            // leading
+            let x = 1 /* inline */ + 2; // trailing
+            /* block /* nested */ */
+            x
+        ";
+
+        for l in [Lexer::new(input.into()), Lexer::new(input.into()).with_comments()] {
+            let mut p = Parser::new(l);
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.to_string(), "let x = (1 + 2);x");
+        }
+    }
+
+    #[test]
+    fn test_numeric_literals() {
+        let tests = vec![
+            ("1_000", "1000"),
+            ("0xff", "255"),
+            ("0xDEAD_BEEF", "3735928559"),
+            ("0o17", "15"),
+            ("0b1010", "10"),
+            ("0x7fff_ffff_ffff_ffff", "9223372036854775807"),
+            ("-9223372036854775808", "(-9223372036854775808)"),
+            ("-0x8000_0000_0000_0000", "(-9223372036854775808)"),
+            ("3.14", "3.14"),
+            ("1e-9", "1e-9"),
+            ("2.5E+3", "2500.0"),
+            ("1_0.5", "10.5"),
+            ("-0.5 * 2", "((-0.5) * 2)"),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.to_string(), expected, "{input}");
+        }
+
+        let mut p = Parser::new(Lexer::new("0.25".into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+
+        let Statement::ExpressionStatement(ref s) = program.statements[0] else {
+            panic!("expected expression statement. Found {:?}", program.statements[0]);
+        };
+        let Expression::FloatLiteral(ref x) = s.value else {
+            panic!("expected float literal. found {s:?}");
+        };
+        assert_eq!(x.value, 0.25);
+    }
+
+    #[test]
+    fn test_malformed_numeric_literals() {
+        let tests = vec![
+            ("0x", "numeric literal `0x` is missing digits"),
+            ("1e;", "numeric literal `1e` is missing digits"),
+            ("0b12", "invalid digit `2` in numeric literal `0b12`"),
+            ("0x1_0000_0000_0000_0000", "integer literal `0x1_0000_0000_0000_0000` is out of range"),
+            ("1e400", "float literal `1e400` is out of range"),
+            ("0b_1", "misplaced `_` in numeric literal `0b_1`"),
+            ("1e_5", "misplaced `_` in numeric literal `1e_5`"),
+            ("1e+_5", "misplaced `_` in numeric literal `1e+_5`"),
+            ("1.5_", "misplaced `_` in numeric literal `1.5_`"),
+        ];
+
+        for (input, error) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let errors = p.parse_program().expect_err("parser should fail");
+
+            assert_eq!(errors.len(), 1, "{errors:?}");
+            assert_eq!(errors[0].to_string(), error);
+        }
+    }
+
+    #[test]
+    fn test_array_literals() {
+        let tests = vec![
+            ("[]", vec![]),
+            ("[1]", vec!["1"]),
+            ("[1, 2 * 2, 3 + 3, \"four\", fn(x) { x }]", vec!["1", "(2 * 2)", "(3 + 3)", "\"four\"", "fn(x) { x }"]),
+        ];
+
+        for (input, elements) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            let Statement::ExpressionStatement(ref s) = program.statements[0] else {
+                panic!("expected expression statement. Found {:?}", program.statements[0]);
+            };
+
+            let Expression::ArrayLiteral(ref x) = s.value else {
+                panic!("expected array literal. found {s:?}");
+            };
+
+            let found: Vec<String> = x.elements.iter().map(|e| e.to_string()).collect();
+            assert_eq!(found, elements);
+        }
+    }
+
+    #[test]
+    fn test_index_expressions() {
+        let input = "[1, 2, 3][0]; my_array[1 + 1]";
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+
+        assert_eq!(program.statements.len(), 2);
+
+        let Statement::ExpressionStatement(ref s) = program.statements[1] else {
+            panic!("expected expression statement. Found {:?}", program.statements[1]);
+        };
+
+        let Expression::Index(ref x) = s.value else {
+            panic!("expected index expression. found {s:?}");
+        };
+
+        assert_eq!(x.left.to_string(), "my_array");
+        assert_eq!(x.index.to_string(), "(1 + 1)");
+        assert_eq!(x.span, Span { start: 14, end: 29, line: 1, column: 15 });
+        assert_eq!(program.statements[0].to_string(), "([1, 2, 3][0])");
+    }
+
+    #[test]
+    fn test_unclosed_array_and_index() {
+        let tests = vec![
+            ("[1, 2", "expected `]`, found end of input"),
+            ("a[1", "expected `]`, found end of input"),
+            ("[1, 2)", "expected `]`, found `)`"),
+        ];
+
+        for (input, error) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let errors = p.parse_program().expect_err("parser should fail");
+
+            assert_eq!(errors[0].to_string(), error);
+        }
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let tests = vec![
+            ("{}", vec![]),
+            (
+                "{\"one\": 1, \"two\": 2, \"three\": 3}",
+                vec![("\"one\"", "1"), ("\"two\"", "2"), ("\"three\"", "3")],
+            ),
+            ("{1: 0 + 1, true: 10 - 8, x: 15 / 5,}", vec![("1", "(0 + 1)"), ("true", "(10 - 8)"), ("x", "(15 / 5)")]),
+            ("{\"nested\": {\"a\": [1]}}", vec![("\"nested\"", "{\"a\": [1]}")]),
+        ];
+
+        for (input, pairs) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.statements.len(), 1);
+
+            let Statement::ExpressionStatement(ref s) = program.statements[0] else {
+                panic!("expected expression statement. Found {:?}", program.statements[0]);
+            };
+
+            let Expression::HashLiteral(ref x) = s.value else {
+                panic!("expected hash literal. found {s:?}");
+            };
+
+            let found: Vec<(String, String)> = x
+                .pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            let expected: Vec<(String, String)> = pairs
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_hash_literals_and_blocks() {
+        let input = "
+            let table = {\"a\": 1};
+            if (x) { {\"b\": 2} } else { table }
+            let f = fn() { {} };
+        ";
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+
+        assert_eq!(
+            program.to_string(),
+            "let table = {\"a\": 1};if x { {\"b\": 2} } else { table }let f = fn() { {} };"
+        );
+    }
+
+    #[test]
+    fn test_malformed_hash_literals() {
+        let tests = vec![
+            ("{1 2}", "expected `:`, found integer literal"),
+            ("{1: 2 3: 4}", "expected `,`, found integer literal"),
+            ("{1: }", "expected expression, found `}`"),
+            ("{1: 2", "expected `,`, found end of input"),
+        ];
+
+        for (input, error) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let errors = p.parse_program().expect_err("parser should fail");
+
+            assert_eq!(errors[0].to_string(), error, "{input}");
+        }
+
+        // A broken hash inside a block must not end the block early.
+        let input = "if (a) { let h = {1: }; h } let y = {2: @, 3: 4}; y";
+
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let errors = p.parse_program().expect_err("parser should fail");
+        assert_eq!(errors.len(), 2, "{errors:?}");
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        let tests = vec![
+            ("[1, 2,]", "[1, 2]"),
+            ("f(1, 2,)", "f(1, 2)"),
+            ("{1: 2, 3: 4,}", "{1: 2, 3: 4}"),
+            ("fn(x, y,) { x }", "fn(x, y) { x }"),
+        ];
+
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let program = p.parse_program().expect("parser should produce valid ast");
+
+            assert_eq!(program.to_string(), expected, "{input}");
+        }
+
+        let tests = vec![
+            ("[,]", "expected expression, found `,`"),
+            ("[1,,]", "expected expression, found `,`"),
+            ("f(,)", "expected expression, found `,`"),
+            ("{,}", "expected expression, found `,`"),
+            ("{1: 2,,}", "expected expression, found `,`"),
+            ("fn(,) { 1 }", "expected identifier, found `,`"),
+        ];
+
+        for (input, error) in tests {
+            let mut p = Parser::new(Lexer::new(input.into()));
+            let errors = p.parse_program().expect_err("parser should fail");
+
+            assert_eq!(errors[0].to_string(), error, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |open: &str, close: &str, depth: usize| {
+            format!("{}1{}", open.repeat(depth), close.repeat(depth))
+        };
+
+        let tests = vec![
+            nested("-", "", MAX_NESTING_DEPTH),
+            nested("(", ")", MAX_NESTING_DEPTH - 1),
+            nested("fn() { ", " }", MAX_NESTING_DEPTH - 1),
+        ];
+
+        for input in tests {
+            let mut p = Parser::new(Lexer::new(input.clone()));
+            p.parse_program().expect("parser should produce valid ast");
+        }
+
+        let tests = vec![
+            (nested("-", "", 200_000), 64),
+            (nested("(", ")", MAX_NESTING_DEPTH), 64),
+            (nested("fn() { ", " }", MAX_NESTING_DEPTH), 448),
+        ];
+
+        for (input, start) in tests {
+            let mut p = Parser::new(Lexer::new(input.clone()));
+            let errors = p.parse_program().expect_err("parser should fail");
+
+            assert_eq!(
+                errors,
+                [ParseError::TooDeep {
+                    limit: MAX_NESTING_DEPTH,
+                    span: Span { start, end: start + 1, line: 1, column: start + 1 },
+                }]
+            );
+            assert_eq!(errors[0].to_string(), "expression is nested too deeply; the limit is 64");
+        }
+    }
 }
@@ -0,0 +1,657 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::code::{read_operands, Bytecode, CompiledFunction, Constant, Instructions, Line, Opcode};
+
+/// The first bytes of every compiled program.
+pub const MAGIC: [u8; 4] = *b"MNKB";
+
+/// Bumped whenever the layout or the instruction set changes incompatibly.
+pub const VERSION: u16 = 1;
+
+/// Set when every chunk of code is followed by its line table.
+const FLAG_LINES: u8 = 0b1;
+
+const TAG_INTEGER: u8 = 0;
+const TAG_FLOAT: u8 = 1;
+const TAG_STRING: u8 = 2;
+
+/// Magic, version and flags.
+const HEADER_LEN: usize = 7;
+const CHECKSUM_LEN: usize = 4;
+
+/// The largest index an instruction operand can hold.
+const MAX_TABLE_LEN: usize = u16::MAX as usize + 1;
+const MAX_LOCALS: usize = u8::MAX as usize + 1;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum LoadError {
+    #[error("not a compiled program")]
+    BadMagic,
+    #[error("unsupported bytecode version {0}; this build reads version {VERSION}")]
+    UnsupportedVersion(u16),
+    #[error("unknown flags {0:#04x}")]
+    UnknownFlags(u8),
+    #[error("unexpected end of input")]
+    Truncated,
+    #[error("checksum mismatch; the file is corrupt")]
+    ChecksumMismatch,
+    #[error("{0} unexpected bytes after the end of the program")]
+    TrailingBytes(usize),
+    #[error("unknown constant tag {0:#04x}")]
+    UnknownConstantTag(u8),
+    #[error("string constant is not valid UTF-8")]
+    InvalidUtf8,
+    #[error("too many {what}; the limit is {limit}")]
+    LimitExceeded { what: &'static str, limit: usize },
+    #[error("invalid {location}: {reason}")]
+    InvalidFunction { location: Location, reason: String },
+    #[error("invalid instruction in {location} at offset {offset:04}: {reason}")]
+    InvalidInstruction {
+        location: Location,
+        offset: usize,
+        reason: String,
+    },
+}
+
+/// Which chunk of code a [`LoadError`] was found in.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Location {
+    Main,
+    Function(usize),
+}
+
+/// Encodes `bytecode` in the binary format read by [`read_bytecode`].
+///
+/// The layout, with every integer big-endian, is:
+///
+/// ```text
+/// magic      "MNKB"
+/// version    u16
+/// flags      u8          bit 0: line tables present
+/// constants  u32 count, then per constant a tag byte and its value
+/// globals    u32 count, then each global's name as a string
+/// functions  u32 count, then per function its parameter, local and free
+///            variable counts (u8, u16, u8) and its code
+/// main       code
+/// checksum   u32         CRC-32 of everything before it
+///
+/// code       u32 length and the instructions, then if flagged
+///            u32 count and that many (u32 offset, u32 line) pairs
+/// string     u32 length and that many bytes of UTF-8
+/// ```
+///
+/// Line tables are only written if `debug_info` is set.
+pub fn write_bytecode(bytecode: &Bytecode, debug_info: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&VERSION.to_be_bytes());
+    out.push(if debug_info { FLAG_LINES } else { 0 });
+
+    write_u32(&mut out, bytecode.constants.len());
+    for constant in &bytecode.constants {
+        match constant {
+            Constant::Integer(x) => {
+                out.push(TAG_INTEGER);
+                out.extend_from_slice(&x.to_be_bytes());
+            }
+            Constant::Float(x) => {
+                out.push(TAG_FLOAT);
+                out.extend_from_slice(&x.to_bits().to_be_bytes());
+            }
+            Constant::String(x) => {
+                out.push(TAG_STRING);
+                write_string(&mut out, x);
+            }
+        }
+    }
+
+    write_u32(&mut out, bytecode.globals.len());
+    for name in &bytecode.globals {
+        write_string(&mut out, name);
+    }
+
+    write_u32(&mut out, bytecode.functions.len());
+    for function in &bytecode.functions {
+        out.push(function.num_parameters as u8);
+        out.extend_from_slice(&(function.num_locals as u16).to_be_bytes());
+        out.push(function.num_free as u8);
+        write_code(&mut out, &function.instructions, &function.lines, debug_info);
+    }
+
+    write_code(&mut out, &bytecode.instructions, &bytecode.lines, debug_info);
+
+    let checksum = crc32(&out);
+    out.extend_from_slice(&checksum.to_be_bytes());
+    out
+}
+
+fn write_code(out: &mut Vec<u8>, instructions: &[u8], lines: &[Line], debug_info: bool) {
+    write_u32(out, instructions.len());
+    out.extend_from_slice(instructions);
+
+    if debug_info {
+        write_u32(out, lines.len());
+        for line in lines {
+            write_u32(out, line.offset);
+            write_u32(out, line.line);
+        }
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_u32(out, value.len());
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_be_bytes());
+}
+
+/// Decodes a program written by [`write_bytecode`].
+///
+/// Everything the VM relies on is checked up front: the checksum, that each
+/// instruction is a known opcode with all of its operands, that constant,
+/// global, function, local and free variable indices are in range, and that
+/// jumps land on an instruction.
+pub fn read_bytecode(bytes: &[u8]) -> Result<Bytecode, LoadError> {
+    if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+        return Err(LoadError::BadMagic);
+    }
+    let mut header = Reader::new(&bytes[MAGIC.len()..]);
+    let version = header.u16()?;
+    if version != VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+    let flags = header.u8()?;
+    if flags & !FLAG_LINES != 0 {
+        return Err(LoadError::UnknownFlags(flags));
+    }
+    let debug_info = flags & FLAG_LINES != 0;
+
+    let body_len = bytes.len().checked_sub(CHECKSUM_LEN).filter(|&len| len >= HEADER_LEN);
+    let Some(body_len) = body_len else {
+        return Err(LoadError::Truncated);
+    };
+    let (body, checksum) = bytes.split_at(body_len);
+    if crc32(body).to_be_bytes() != checksum {
+        return Err(LoadError::ChecksumMismatch);
+    }
+
+    let mut reader = Reader::new(&body[HEADER_LEN..]);
+
+    let num_constants = reader.count("constants", MAX_TABLE_LEN)?;
+    let mut constants = Vec::new();
+    for _ in 0..num_constants {
+        let constant = match reader.u8()? {
+            TAG_INTEGER => Constant::Integer(i64::from_be_bytes(reader.array()?)),
+            TAG_FLOAT => Constant::Float(f64::from_bits(u64::from_be_bytes(reader.array()?))),
+            TAG_STRING => Constant::String(reader.string()?),
+            tag => return Err(LoadError::UnknownConstantTag(tag)),
+        };
+        constants.push(constant);
+    }
+
+    let num_globals = reader.count("globals", MAX_TABLE_LEN)?;
+    let mut globals = Vec::new();
+    for _ in 0..num_globals {
+        globals.push(reader.string()?);
+    }
+
+    let num_functions = reader.count("functions", MAX_TABLE_LEN)?;
+    let mut functions = Vec::new();
+    for _ in 0..num_functions {
+        let num_parameters = reader.u8()? as usize;
+        let num_locals = reader.u16()? as usize;
+        let num_free = reader.u8()? as usize;
+        let (instructions, lines) = reader.code(debug_info)?;
+        functions.push(CompiledFunction {
+            instructions,
+            num_locals,
+            num_parameters,
+            num_free,
+            lines,
+        });
+    }
+
+    let (instructions, lines) = reader.code(debug_info)?;
+    if reader.remaining() > 0 {
+        return Err(LoadError::TrailingBytes(reader.remaining()));
+    }
+
+    let bytecode = Bytecode {
+        instructions,
+        lines,
+        constants,
+        globals,
+        functions,
+    };
+    validate(&bytecode)?;
+    Ok(bytecode)
+}
+
+fn validate(bytecode: &Bytecode) -> Result<(), LoadError> {
+    for (i, function) in bytecode.functions.iter().enumerate() {
+        let location = Location::Function(i);
+        if function.num_locals > MAX_LOCALS {
+            return Err(LoadError::InvalidFunction {
+                location,
+                reason: format!("{} locals is more than the limit of {MAX_LOCALS}", function.num_locals),
+            });
+        }
+        if function.num_parameters > function.num_locals {
+            return Err(LoadError::InvalidFunction {
+                location,
+                reason: format!(
+                    "{} parameters do not fit in {} locals",
+                    function.num_parameters, function.num_locals
+                ),
+            });
+        }
+        validate_code(bytecode, location, &function.instructions, &function.lines, function)?;
+    }
+
+    let main = CompiledFunction::default();
+    validate_code(bytecode, Location::Main, &bytecode.instructions, &bytecode.lines, &main)
+}
+
+/// Checks that `ins` decodes cleanly and only refers to things that exist.
+fn validate_code(
+    bytecode: &Bytecode,
+    location: Location,
+    ins: &[u8],
+    lines: &[Line],
+    function: &CompiledFunction,
+) -> Result<(), LoadError> {
+    let invalid = |offset: usize, reason: String| LoadError::InvalidInstruction {
+        location,
+        offset,
+        reason,
+    };
+
+    let mut boundaries = vec![false; ins.len() + 1];
+    let mut jumps = Vec::new();
+    let mut offset = 0;
+
+    while offset < ins.len() {
+        boundaries[offset] = true;
+
+        let op = Opcode::from_byte(ins[offset])
+            .ok_or_else(|| invalid(offset, format!("unknown opcode {:#04x}", ins[offset])))?;
+        let (operands, read) = read_operands(op, &ins[offset + 1..])
+            .ok_or_else(|| invalid(offset, format!("{op} is missing operands")))?;
+
+        match op {
+            Opcode::Constant if operands[0] >= bytecode.constants.len() => {
+                return Err(invalid(offset, format!("constant {} does not exist", operands[0])));
+            }
+            Opcode::GetGlobal | Opcode::SetGlobal if operands[0] >= bytecode.globals.len() => {
+                return Err(invalid(offset, format!("global {} does not exist", operands[0])));
+            }
+            Opcode::GetLocal | Opcode::SetLocal if operands[0] >= function.num_locals => {
+                return Err(invalid(offset, format!("local {} does not exist", operands[0])));
+            }
+            Opcode::GetFree if operands[0] >= function.num_free => {
+                return Err(invalid(offset, format!("free variable {} does not exist", operands[0])));
+            }
+            Opcode::Closure => match bytecode.functions.get(operands[0]) {
+                None => return Err(invalid(offset, format!("function {} does not exist", operands[0]))),
+                Some(target) if target.num_free != operands[1] => {
+                    return Err(invalid(
+                        offset,
+                        format!(
+                            "function {} captures {} free variables, not {}",
+                            operands[0], target.num_free, operands[1]
+                        ),
+                    ));
+                }
+                Some(_) => {}
+            },
+            Opcode::Jump | Opcode::JumpNotTruthy => jumps.push((offset, operands[0])),
+            _ => {}
+        }
+
+        offset += 1 + read;
+    }
+    // Jumping to the very end is how an `if` at the end of a chunk finishes.
+    boundaries[ins.len()] = true;
+
+    for (offset, target) in jumps {
+        if !boundaries.get(target).copied().unwrap_or(false) {
+            return Err(invalid(offset, format!("jump target {target:04} is not an instruction")));
+        }
+    }
+
+    let mut previous = None;
+    for line in lines {
+        if line.offset >= ins.len() || previous.is_some_and(|previous| line.offset <= previous) {
+            return Err(LoadError::InvalidFunction {
+                location,
+                reason: format!("line table entry for offset {:04} is out of order", line.offset),
+            });
+        }
+        previous = Some(line.offset);
+    }
+
+    Ok(())
+}
+
+/// A cursor over the encoded bytes that fails cleanly on truncated input.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes }
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+        if len > self.bytes.len() {
+            return Err(LoadError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], LoadError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, LoadError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<usize, LoadError> {
+        Ok(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn string(&mut self) -> Result<String, LoadError> {
+        let len = self.u32()?;
+        let bytes = self.take(len)?;
+        let value = std::str::from_utf8(bytes).map_err(|_| LoadError::InvalidUtf8)?;
+        Ok(value.to_string())
+    }
+
+    fn count(&mut self, what: &'static str, limit: usize) -> Result<usize, LoadError> {
+        let count = self.u32()?;
+        if count > limit {
+            return Err(LoadError::LimitExceeded { what, limit });
+        }
+        Ok(count)
+    }
+
+    fn code(&mut self, debug_info: bool) -> Result<(Instructions, Vec<Line>), LoadError> {
+        let len = self.u32()?;
+        let instructions = self.take(len)?.to_vec();
+
+        let mut lines = Vec::new();
+        if debug_info {
+            // Valid entries have distinct offsets inside the code, so there
+            // can be no more of them than bytes of code.
+            let count = self.count("line table entries", instructions.len())?;
+            for _ in 0..count {
+                let offset = self.u32()?;
+                let line = self.u32()?;
+                lines.push(Line { offset, line });
+            }
+        }
+
+        Ok((instructions, lines))
+    }
+}
+
+/// CRC-32 as used by zlib and PNG.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Main => f.write_str("main"),
+            Location::Function(i) => write!(f, "fn {i}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{crc32, read_bytecode, write_bytecode, LoadError, Location, VERSION};
+    use crate::code::{make, Bytecode, CompiledFunction, Constant, Opcode};
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::object::Object;
+    use crate::parser::Parser;
+    use crate::vm::Vm;
+
+    const PROGRAM: &str = "
+        let greeting = \"héllo\";
+        let adder = fn(x) { fn(y) { x + y } };
+        let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) };
+        [adder(1.5)(2), fib(10), {greeting: -9223372036854775808}]
+    ";
+
+    fn compile(input: &str) -> Bytecode {
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+        Compiler::new().compile(&program).expect("compiler should succeed")
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytecode = compile(PROGRAM);
+        assert!(!bytecode.lines.is_empty());
+
+        let loaded = read_bytecode(&write_bytecode(&bytecode, true)).unwrap();
+        assert_eq!(loaded, bytecode);
+        assert_eq!(
+            Vm::new(&loaded).run().unwrap().to_string(),
+            "[3.5, 55, {\"héllo\": -9223372036854775808}]"
+        );
+
+        let stripped = read_bytecode(&write_bytecode(&bytecode, false)).unwrap();
+        assert!(stripped.lines.is_empty());
+        assert!(stripped.functions.iter().all(|f| f.lines.is_empty()));
+        assert_eq!(stripped.instructions, bytecode.instructions);
+        assert_eq!(Vm::new(&stripped).run().unwrap(), Vm::new(&bytecode).run().unwrap());
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let bytes = write_bytecode(&compile(PROGRAM), true);
+
+        for len in 0..bytes.len() {
+            assert!(read_bytecode(&bytes[..len]).is_err(), "{len}");
+        }
+    }
+
+    #[test]
+    fn test_corrupted_input() {
+        let bytes = write_bytecode(&compile(PROGRAM), true);
+
+        for i in 0..bytes.len() {
+            let mut corrupted = bytes.clone();
+            corrupted[i] ^= 0x20;
+            assert!(read_bytecode(&corrupted).is_err(), "{i}");
+        }
+
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert_eq!(read_bytecode(&extended), Err(LoadError::ChecksumMismatch));
+    }
+
+    #[test]
+    fn test_header_errors() {
+        let bytes = write_bytecode(&Bytecode::default(), false);
+        assert_eq!(read_bytecode(&bytes).unwrap(), Bytecode::default());
+
+        assert_eq!(read_bytecode(b"#!/usr/bin/env monkey"), Err(LoadError::BadMagic));
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(VERSION + 1).to_be_bytes());
+        assert_eq!(read_bytecode(&newer), Err(LoadError::UnsupportedVersion(VERSION + 1)));
+
+        let mut flagged = bytes.clone();
+        flagged[6] = 0x80;
+        assert_eq!(read_bytecode(&flagged), Err(LoadError::UnknownFlags(0x80)));
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let mut bytes = write_bytecode(&Bytecode::default(), false);
+        bytes.truncate(bytes.len() - 4);
+        bytes.push(0);
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_be_bytes());
+
+        assert_eq!(read_bytecode(&bytes), Err(LoadError::TrailingBytes(1)));
+    }
+
+    /// Malformed programs that still carry a valid checksum, as a tool that
+    /// writes bad bytecode would produce.
+    #[test]
+    fn test_invalid_code() {
+        let function = |instructions: Vec<u8>, num_locals, num_free| CompiledFunction {
+            instructions,
+            num_locals,
+            num_parameters: 0,
+            num_free,
+            lines: Vec::new(),
+        };
+        let main = |instructions: Vec<u8>| Bytecode {
+            instructions,
+            ..Bytecode::default()
+        };
+
+        let tests = [
+            (main(vec![0xff]), "invalid instruction in main at offset 0000: unknown opcode 0xff"),
+            (
+                main(vec![Opcode::Pop as u8, Opcode::Constant as u8, 0]),
+                "invalid instruction in main at offset 0001: OpConstant is missing operands",
+            ),
+            (
+                main(make(Opcode::Constant, &[0])),
+                "invalid instruction in main at offset 0000: constant 0 does not exist",
+            ),
+            (
+                main(make(Opcode::GetGlobal, &[0])),
+                "invalid instruction in main at offset 0000: global 0 does not exist",
+            ),
+            (
+                main(make(Opcode::GetLocal, &[0])),
+                "invalid instruction in main at offset 0000: local 0 does not exist",
+            ),
+            (
+                main(make(Opcode::Closure, &[0, 0])),
+                "invalid instruction in main at offset 0000: function 0 does not exist",
+            ),
+            (
+                main([make(Opcode::True, &[]), make(Opcode::JumpNotTruthy, &[2])].concat()),
+                "invalid instruction in main at offset 0001: jump target 0002 is not an instruction",
+            ),
+            (
+                main(make(Opcode::Jump, &[4])),
+                "invalid instruction in main at offset 0000: jump target 0004 is not an instruction",
+            ),
+            (
+                Bytecode {
+                    instructions: make(Opcode::Closure, &[0, 0]),
+                    functions: vec![function(make(Opcode::GetFree, &[0]), 0, 1)],
+                    ..Bytecode::default()
+                },
+                "invalid instruction in main at offset 0000: function 0 captures 1 free variables, not 0",
+            ),
+            (
+                Bytecode {
+                    functions: vec![function(make(Opcode::GetFree, &[1]), 0, 1)],
+                    ..Bytecode::default()
+                },
+                "invalid instruction in fn 0 at offset 0000: free variable 1 does not exist",
+            ),
+            (
+                Bytecode {
+                    functions: vec![CompiledFunction {
+                        num_parameters: 2,
+                        ..function(make(Opcode::Return, &[]), 1, 0)
+                    }],
+                    ..Bytecode::default()
+                },
+                "invalid fn 0: 2 parameters do not fit in 1 locals",
+            ),
+        ];
+
+        for (bytecode, expected) in tests {
+            let err = read_bytecode(&write_bytecode(&bytecode, false)).unwrap_err();
+            assert_eq!(err.to_string(), expected);
+        }
+
+        let mut bytecode = compile("1;\n2;");
+        bytecode.lines.reverse();
+        assert_eq!(
+            read_bytecode(&write_bytecode(&bytecode, true)),
+            Err(LoadError::InvalidFunction {
+                location: Location::Main,
+                reason: "line table entry for offset 0000 is out of order".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_constants() {
+        let bytecode = Bytecode {
+            constants: vec![Constant::String("ok".to_string())],
+            ..Bytecode::default()
+        };
+        let bytes = write_bytecode(&bytecode, false);
+        // The string's bytes follow the 7 byte header, the constant count,
+        // the tag and the length.
+        let string_start = 7 + 4 + 1 + 4;
+
+        let reseal = |mut bytes: Vec<u8>| {
+            let body = bytes.len() - 4;
+            let checksum = crc32(&bytes[..body]);
+            bytes[body..].copy_from_slice(&checksum.to_be_bytes());
+            bytes
+        };
+
+        let mut invalid_utf8 = bytes.clone();
+        invalid_utf8[string_start] = 0xff;
+        assert_eq!(read_bytecode(&reseal(invalid_utf8)), Err(LoadError::InvalidUtf8));
+
+        let mut unknown_tag = bytes.clone();
+        unknown_tag[string_start - 5] = 9;
+        assert_eq!(read_bytecode(&reseal(unknown_tag)), Err(LoadError::UnknownConstantTag(9)));
+
+        let mut huge_count = bytes.clone();
+        huge_count[7..11].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(
+            read_bytecode(&reseal(huge_count)),
+            Err(LoadError::LimitExceeded {
+                what: "constants",
+                limit: 65536
+            })
+        );
+
+        assert_eq!(Vm::new(&read_bytecode(&bytes).unwrap()).run(), Ok(Object::Null));
+    }
+}
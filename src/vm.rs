@@ -0,0 +1,414 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use thiserror::Error;
+
+use crate::ast::Operator;
+use crate::code::{read_u16, Bytecode, CompiledFunction, Constant, Opcode};
+use crate::evaluator::{eval_index, eval_infix, eval_prefix};
+use crate::object::{Closure, Object};
+
+const STACK_SIZE: usize = 2048;
+const MAX_FRAMES: usize = 1024;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum VmError {
+    /// An error raised by the program itself, worded as the evaluator words it.
+    #[error("{0}")]
+    Runtime(String),
+    #[error("stack overflow")]
+    StackOverflow,
+    #[error("stack underflow")]
+    StackUnderflow,
+    #[error("invalid opcode {0:#04x}")]
+    InvalidOpcode(u8),
+}
+
+/// The state of a function call that is waiting on a callee to return.
+#[derive(Debug)]
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    base: usize,
+}
+
+/// A stack machine that executes [`Bytecode`].
+#[derive(Debug)]
+pub struct Vm {
+    constants: Vec<Object>,
+    functions: Vec<Rc<CompiledFunction>>,
+    main: Rc<Closure>,
+    stack: Vec<Object>,
+    /// `None` until the global is first bound.
+    globals: Vec<Option<Object>>,
+    global_names: Vec<String>,
+    frames: Vec<Frame>,
+    last_popped: Object,
+}
+
+impl Vm {
+    pub fn new(bytecode: &Bytecode) -> Vm {
+        let constants = bytecode
+            .constants
+            .iter()
+            .map(|constant| match constant {
+                Constant::Integer(x) => Object::Integer(*x),
+                Constant::Float(x) => Object::Float(*x),
+                Constant::String(x) => Object::String(x.clone()),
+            })
+            .collect();
+        let main = CompiledFunction {
+            instructions: bytecode.instructions.clone(),
+            ..CompiledFunction::default()
+        };
+
+        Vm {
+            constants,
+            functions: bytecode.functions.iter().cloned().map(Rc::new).collect(),
+            main: Rc::new(Closure {
+                function: Rc::new(main),
+                free: Vec::new(),
+            }),
+            stack: Vec::with_capacity(STACK_SIZE),
+            globals: vec![None; bytecode.globals.len()],
+            global_names: bytecode.globals.clone(),
+            frames: Vec::new(),
+            last_popped: Object::Null,
+        }
+    }
+
+    /// Runs the program, returning the value of its last expression statement
+    /// or of a top-level `return`.
+    pub fn run(&mut self) -> Result<Object, VmError> {
+        // The running frame lives in locals; `frames` only holds its callers.
+        let mut closure = Rc::clone(&self.main);
+        let mut ip = 0;
+        let mut base: usize = 0;
+
+        loop {
+            let instructions = &closure.function.instructions;
+            let Some(&byte) = instructions.get(ip) else {
+                // Compiled functions always return explicitly, so only the
+                // main program runs off its end.
+                if self.frames.is_empty() {
+                    return Ok(self.last_popped.clone());
+                }
+                self.stack.truncate(base.saturating_sub(1));
+                self.push(Object::Null)?;
+                (closure, ip, base) = self.pop_frame();
+                continue;
+            };
+            let op = Opcode::from_byte(byte).ok_or(VmError::InvalidOpcode(byte))?;
+            let operand = ip + 1;
+            ip += op.width();
+
+            match op {
+                Opcode::Constant => {
+                    let constant = self.constants[read_u16(instructions, operand)].clone();
+                    self.push(constant)?;
+                }
+                Opcode::Pop => self.last_popped = self.pop()?,
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::GreaterThan
+                | Opcode::LessThan => {
+                    let operator = match op {
+                        Opcode::Add => Operator::Plus,
+                        Opcode::Sub => Operator::Minus,
+                        Opcode::Mul => Operator::Asterisk,
+                        Opcode::Div => Operator::Slash,
+                        Opcode::Equal => Operator::Eq,
+                        Opcode::NotEqual => Operator::NotEq,
+                        Opcode::GreaterThan => Operator::Gt,
+                        _ => Operator::Lt,
+                    };
+                    let right = self.pop()?;
+                    let left = self.pop()?;
+                    self.push_result(eval_infix(&operator, left, right))?;
+                }
+                Opcode::Minus | Opcode::Bang => {
+                    let operator = if op == Opcode::Minus { Operator::Minus } else { Operator::Bang };
+                    let right = self.pop()?;
+                    self.push_result(eval_prefix(&operator, right))?;
+                }
+                Opcode::True => self.push(Object::Boolean(true))?,
+                Opcode::False => self.push(Object::Boolean(false))?,
+                Opcode::Null => self.push(Object::Null)?,
+                Opcode::JumpNotTruthy => {
+                    if !self.pop()?.is_truthy() {
+                        ip = read_u16(instructions, operand);
+                    }
+                }
+                Opcode::Jump => ip = read_u16(instructions, operand),
+                Opcode::GetGlobal => {
+                    let index = read_u16(instructions, operand);
+                    let Some(Some(global)) = self.globals.get(index) else {
+                        let name = self.global_names.get(index).map_or("<unnamed>", String::as_str);
+                        return Err(VmError::Runtime(format!("identifier not found: {name}")));
+                    };
+                    let global = global.clone();
+                    self.push(global)?;
+                }
+                Opcode::SetGlobal => {
+                    let index = read_u16(instructions, operand);
+                    if index >= self.globals.len() {
+                        self.globals.resize(index + 1, None);
+                    }
+                    self.globals[index] = Some(self.pop()?);
+                }
+                Opcode::GetLocal => {
+                    let local = self.stack.get(base + instructions[operand] as usize).cloned();
+                    self.push(local.ok_or(VmError::StackUnderflow)?)?;
+                }
+                Opcode::SetLocal => {
+                    let value = self.pop()?;
+                    let local = self.stack.get_mut(base + instructions[operand] as usize);
+                    *local.ok_or(VmError::StackUnderflow)? = value;
+                }
+                Opcode::GetFree => {
+                    let free = closure.free[instructions[operand] as usize].clone();
+                    self.push(free)?;
+                }
+                Opcode::CurrentClosure => self.push(Object::Closure(Rc::clone(&closure)))?,
+                Opcode::Array => {
+                    let elements = self.pop_many(read_u16(instructions, operand))?;
+                    self.push(Object::Array(elements))?;
+                }
+                Opcode::Hash => {
+                    let items = self.pop_many(read_u16(instructions, operand) * 2)?;
+                    let mut pairs = BTreeMap::new();
+                    let mut items = items.into_iter();
+                    while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                        let Some(key) = key.hash_key() else {
+                            return Err(VmError::Runtime(format!("unusable as hash key: {}", key.type_name())));
+                        };
+                        pairs.insert(key, value);
+                    }
+                    self.push(Object::Hash(pairs))?;
+                }
+                Opcode::Index => {
+                    let index = self.pop()?;
+                    let left = self.pop()?;
+                    self.push_result(eval_index(left, index))?;
+                }
+                Opcode::Call => {
+                    let num_arguments = instructions[operand] as usize;
+                    let callee = self
+                        .stack
+                        .len()
+                        .checked_sub(num_arguments + 1)
+                        .ok_or(VmError::StackUnderflow)?;
+                    let Object::Closure(callee_closure) = &self.stack[callee] else {
+                        return Err(VmError::Runtime(format!(
+                            "not a function: {}",
+                            self.stack[callee].type_name()
+                        )));
+                    };
+                    let callee_closure = Rc::clone(callee_closure);
+
+                    let function = &callee_closure.function;
+                    if function.num_parameters != num_arguments {
+                        return Err(VmError::Runtime(format!(
+                            "wrong number of arguments: expected {}, got {}",
+                            function.num_parameters, num_arguments
+                        )));
+                    }
+                    if self.frames.len() >= MAX_FRAMES {
+                        return Err(VmError::StackOverflow);
+                    }
+
+                    let callee_base = callee + 1;
+                    let stack_top = callee_base + function.num_locals.max(num_arguments);
+                    if stack_top > STACK_SIZE {
+                        return Err(VmError::StackOverflow);
+                    }
+                    self.stack.resize(stack_top, Object::Null);
+
+                    let caller = std::mem::replace(&mut closure, callee_closure);
+                    self.frames.push(Frame {
+                        closure: caller,
+                        ip,
+                        base,
+                    });
+                    ip = 0;
+                    base = callee_base;
+                }
+                Opcode::ReturnValue | Opcode::Return => {
+                    let value = if op == Opcode::ReturnValue { self.pop()? } else { Object::Null };
+                    if self.frames.is_empty() {
+                        return Ok(value);
+                    }
+
+                    // Drop the callee's locals along with the callee itself.
+                    self.stack.truncate(base - 1);
+                    self.push(value)?;
+                    (closure, ip, base) = self.pop_frame();
+                }
+                Opcode::Closure => {
+                    let function = Rc::clone(&self.functions[read_u16(instructions, operand)]);
+                    let free = self.pop_many(instructions[operand + 2] as usize)?;
+                    self.push(Object::Closure(Rc::new(Closure { function, free })))?;
+                }
+            }
+        }
+    }
+
+    fn pop_frame(&mut self) -> (Rc<Closure>, usize, usize) {
+        let frame = self.frames.pop().expect("returning from a called function");
+        (frame.closure, frame.ip, frame.base)
+    }
+
+    fn push(&mut self, object: Object) -> Result<(), VmError> {
+        if self.stack.len() >= STACK_SIZE {
+            return Err(VmError::StackOverflow);
+        }
+        self.stack.push(object);
+        Ok(())
+    }
+
+    /// Pushes the result of an operation, turning error objects into errors.
+    fn push_result(&mut self, object: Object) -> Result<(), VmError> {
+        match object {
+            Object::Error(message) => Err(VmError::Runtime(message)),
+            object => self.push(object),
+        }
+    }
+
+    fn pop(&mut self) -> Result<Object, VmError> {
+        self.stack.pop().ok_or(VmError::StackUnderflow)
+    }
+
+    /// Pops the top `count` objects, returning them in the order they were pushed.
+    fn pop_many(&mut self, count: usize) -> Result<Vec<Object>, VmError> {
+        let start = self.stack.len().checked_sub(count).ok_or(VmError::StackUnderflow)?;
+        Ok(self.stack.split_off(start))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Vm, VmError};
+    use crate::compiler::Compiler;
+    use crate::environment::Environment;
+    use crate::evaluator::eval;
+    use crate::lexer::Lexer;
+    use crate::object::Object;
+    use crate::parser::{Parser, MAX_NESTING_DEPTH};
+
+    fn run(input: &str) -> Result<Object, VmError> {
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+        let bytecode = Compiler::new().compile(&program).expect("compiler should succeed");
+        Vm::new(&bytecode).run()
+    }
+
+    /// Runs `input` through both engines, rendering results and errors alike.
+    fn run_both(input: &str) -> (String, String) {
+        let mut p = Parser::new(Lexer::new(input.into()));
+        let program = p.parse_program().expect("parser should produce valid ast");
+        let evaluated = eval(&program, &Environment::new()).to_string();
+        let executed = match run(input) {
+            Ok(result) => result.to_string(),
+            Err(err) => format!("error: {err}"),
+        };
+        (evaluated, executed)
+    }
+
+    #[test]
+    fn test_engines_agree() {
+        let tests = [
+            "1 + 2 * 3 - 4 / 2",
+            "-9223372036854775808",
+            "1.5 * 2 + 1",
+            "!(1 < 2) == (3 > 4)",
+            "\"mon\" + \"key\"",
+            "if (1 > 2) { 10 }",
+            "if (1 > 2) { 10 } else { 20 }",
+            "if (true) { let a = 1; }",
+            "let a = 1; let b = a + 1; [a, b, a * b][2]",
+            "{\"one\": 1, true: 2, 3: [3]}[3][0]",
+            "{1: 2}[2]",
+            "return 1; 2",
+            "if (true) { if (true) { return 10; } return 1; }",
+            "let identity = fn(x) { x }; identity(5)",
+            "let f = fn() { return 1; 2 }; f() + 10",
+            "let f = fn() { }; f()",
+            "let f = fn() { let x = 1; }; f()",
+            "let adder = fn(x) { fn(y) { x + y } }; adder(1)(2) + adder(10)(20)",
+            "let x = 1; let f = fn(x) { x }; f(2) + x",
+            "let x = 1; let f = fn(y) { let x = x + y; x }; f(5) * 10 + x",
+            "let x = 1; let get = fn() { x }; let call = fn(x) { get() }; call(100)",
+            "let f = fn(a) { fn(b) { fn(c) { a + b + c } } }; f(1)(2)(3)",
+            "let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib(15)",
+            "let wrap = fn() { let countdown = fn(n) { if (n == 0) { 0 } else { countdown(n - 1) } }; countdown(5) }; wrap()",
+            // Globals are looked up when they are read.
+            "let f = fn() { g() }; let g = fn() { 1 }; f();",
+            "let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } }; let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } }; even(10)",
+            "let x = 1; let f = fn() { x }; let x = 2; f();",
+            "let f = fn() { 1 }; let g = fn() { f() }; let f = fn() { 2 }; g()",
+            "let f = fn() { y }; f()",
+            "let f = fn() { let z = 1; }; f(); z",
+            // Errors are worded the same way.
+            "foobar",
+            "5 + true",
+            "-true",
+            "\"a\" - \"b\"",
+            "1 / 0",
+            "9223372036854775807 + 1",
+            "{[1]: 2}",
+            "1[0]",
+            "let f = fn(x) { x }; f(1, 2)",
+            "5(1)",
+        ];
+
+        for input in tests {
+            let (evaluated, executed) = run_both(input);
+            assert_eq!(executed, evaluated, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_deepest_nesting_fits_default_stack() {
+        // Test threads get the default 2 MiB stack, so this checks that the
+        // parser's nesting limit keeps every stage within it.
+        let depth = MAX_NESTING_DEPTH - 1;
+        let tests = [
+            format!("{}1{}", "fn() { ".repeat(depth), " }()".repeat(depth)),
+            format!("{}1{}", "if (true) { ".repeat(depth), " }".repeat(depth)),
+            format!("{}1{}", "[".repeat(depth), "]".repeat(depth)),
+        ];
+
+        for input in tests {
+            let (evaluated, executed) = run_both(&input);
+            assert!(!evaluated.starts_with("error"), "{evaluated}");
+            assert_eq!(executed, evaluated);
+        }
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let tests = [
+            ("5 + true", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("1 / 0", "division by zero"),
+            ("9223372036854775807 + 1", "integer overflow: 9223372036854775807 + 1"),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("1[0]", "index operator not supported: INTEGER[INTEGER]"),
+            ("let f = fn(x) { x }; f(1, 2)", "wrong number of arguments: expected 1, got 2"),
+            ("5(1)", "not a function: INTEGER"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(run(input), Err(VmError::Runtime(expected.to_string())), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        assert_eq!(run("let f = fn() { f() + 1 }; f()"), Err(VmError::StackOverflow));
+    }
+}